@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE tipo_viviendas ADD COLUMN deleted_at VARCHAR;
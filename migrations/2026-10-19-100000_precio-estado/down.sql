@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE tipo_viviendas DROP COLUMN descripcion;
+ALTER TABLE tipo_viviendas DROP COLUMN fecha_alta;
+ALTER TABLE tipo_viviendas DROP COLUMN estado;
+ALTER TABLE tipo_viviendas DROP COLUMN moneda;
+ALTER TABLE tipo_viviendas DROP COLUMN precio;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE tipo_viviendas ADD COLUMN precio DOUBLE NOT NULL DEFAULT 0;
+ALTER TABLE tipo_viviendas ADD COLUMN moneda VARCHAR NOT NULL DEFAULT 'EUR';
+ALTER TABLE tipo_viviendas ADD COLUMN estado VARCHAR NOT NULL DEFAULT 'Disponible';
+ALTER TABLE tipo_viviendas ADD COLUMN fecha_alta VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE tipo_viviendas ADD COLUMN descripcion VARCHAR NOT NULL DEFAULT '';
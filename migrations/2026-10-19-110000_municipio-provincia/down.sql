@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE tipo_viviendas DROP COLUMN provincia;
+ALTER TABLE tipo_viviendas DROP COLUMN municipio;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE tipo_viviendas ADD COLUMN municipio VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE tipo_viviendas ADD COLUMN provincia VARCHAR NOT NULL DEFAULT '';
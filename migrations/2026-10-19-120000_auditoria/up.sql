@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE auditoria (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    operacion VARCHAR NOT NULL,
+    identificacion VARCHAR NOT NULL,
+    antes TEXT,
+    despues TEXT,
+    fecha VARCHAR NOT NULL,
+    usuario VARCHAR NOT NULL
+);
+CREATE INDEX auditoria_identificacion ON auditoria (identificacion);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE tipo_viviendas DROP COLUMN updated_at;
+ALTER TABLE tipo_viviendas DROP COLUMN version;
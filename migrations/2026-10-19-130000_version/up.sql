@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE tipo_viviendas ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE tipo_viviendas ADD COLUMN updated_at VARCHAR NOT NULL DEFAULT '';
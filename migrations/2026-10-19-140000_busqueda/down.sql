@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER tipo_viviendas_fts_update;
+DROP TRIGGER tipo_viviendas_fts_delete;
+DROP TRIGGER tipo_viviendas_fts_insert;
+DROP TABLE tipo_viviendas_fts;
@@ -0,0 +1,33 @@
+-- Your SQL goes here
+-- Índice de texto completo de las viviendas; los disparadores lo mantienen al día
+CREATE VIRTUAL TABLE tipo_viviendas_fts USING fts5(
+    identificacion,
+    calle,
+    numero,
+    piso,
+    codigo_postal,
+    municipio,
+    provincia,
+    tipo,
+    estado,
+    descripcion,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO tipo_viviendas_fts (identificacion, calle, numero, piso, codigo_postal, municipio, provincia, tipo, estado, descripcion)
+    SELECT identificacion, calle, numero, piso, codigo_postal, municipio, provincia, tipo, estado, descripcion FROM tipo_viviendas;
+
+CREATE TRIGGER tipo_viviendas_fts_insert AFTER INSERT ON tipo_viviendas BEGIN
+    INSERT INTO tipo_viviendas_fts (identificacion, calle, numero, piso, codigo_postal, municipio, provincia, tipo, estado, descripcion)
+        VALUES (new.identificacion, new.calle, new.numero, new.piso, new.codigo_postal, new.municipio, new.provincia, new.tipo, new.estado, new.descripcion);
+END;
+
+CREATE TRIGGER tipo_viviendas_fts_delete AFTER DELETE ON tipo_viviendas BEGIN
+    DELETE FROM tipo_viviendas_fts WHERE identificacion = old.identificacion;
+END;
+
+CREATE TRIGGER tipo_viviendas_fts_update AFTER UPDATE ON tipo_viviendas BEGIN
+    DELETE FROM tipo_viviendas_fts WHERE identificacion = old.identificacion;
+    INSERT INTO tipo_viviendas_fts (identificacion, calle, numero, piso, codigo_postal, municipio, provincia, tipo, estado, descripcion)
+        VALUES (new.identificacion, new.calle, new.numero, new.piso, new.codigo_postal, new.municipio, new.provincia, new.tipo, new.estado, new.descripcion);
+END;
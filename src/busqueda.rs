@@ -0,0 +1,120 @@
+use crate::direccion::fold;
+use crate::entidad::TipoVivienda;
+
+// Palabras de un texto sin tildes ni mayúsculas: "C/ José-Antonio, 4" -> ["c", "jose", "antonio", "4"]
+pub fn words(texto: &str) -> Vec<String> {
+    fold(texto)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|palabra| !palabra.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+// Distancia de edición contando el cambio de dos letras contiguas como una sola errata: "jsoe" está a 1 de "jose"
+pub fn distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, fila) in d.iter_mut().enumerate() {
+        fila[0] = i;
+    }
+    for (j, celda) in d[0].iter_mut().enumerate() {
+        *celda = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let coste = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + coste);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+// Erratas que se toleran según la longitud de la palabra buscada; en las cortas ninguna
+fn tolerance(palabra: &str) -> usize {
+    match palabra.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+// Parecido entre 0 y 1 de una palabra buscada con una de la vivienda: igual, principio de la palabra
+// o, si se admiten erratas, a poca distancia de edición
+fn similarity(buscada: &str, palabra: &str, erratas: bool) -> Option<f64> {
+    if buscada == palabra {
+        return Some(1.0);
+    }
+    if palabra.starts_with(buscada) {
+        return Some(0.5 + 0.4 * buscada.chars().count() as f64 / palabra.chars().count() as f64);
+    }
+    if erratas {
+        let distancia = distance(buscada, palabra);
+        if distancia <= tolerance(buscada) {
+            return Some(0.8 - 0.2 * distancia as f64);
+        }
+    }
+    None
+}
+
+// Media del parecido de cada palabra buscada con la mejor de la vivienda; None si alguna no aparece.
+// Las erratas solo se admiten en la calle: en números o códigos postales darían falsos positivos
+pub fn score(p: &TipoVivienda, texto: &str) -> Option<f64> {
+    let buscadas = words(texto);
+    if buscadas.is_empty() {
+        return Some(1.0);
+    }
+    let calle = words(&p.calle);
+    let resto = words(&[
+        p.identificacion.clone(), p.numero.to_string(), p.piso.clone(), p.codigo_postal.clone(),
+        p.municipio.clone(), p.provincia.clone(), p.tipo.to_string(), p.estado.to_string(), p.descripcion.clone(),
+    ].join(" "));
+    let mut total = 0.0;
+    for buscada in &buscadas {
+        let mejor = calle.iter().filter_map(|palabra| similarity(buscada, palabra, true))
+            .chain(resto.iter().filter_map(|palabra| similarity(buscada, palabra, false)))
+            .max_by(|a, b| a.total_cmp(b));
+        total += mejor?;
+    }
+    Some(total / buscadas.len() as f64)
+}
+
+// Posiciones en datos de las viviendas que encajan con el texto, de la más a la menos parecida;
+// a igual puntuación se respeta el orden de datos
+pub fn rank(datos: &[TipoVivienda], texto: &str) -> Vec<usize> {
+    let mut puntuadas: Vec<(usize, f64)> = datos.iter().enumerate()
+        .filter_map(|(i, p)| score(p, texto).map(|puntuacion| (i, puntuacion)))
+        .collect();
+    puntuadas.sort_by(|a, b| b.1.total_cmp(&a.1));
+    puntuadas.into_iter().map(|(i, _)| i).collect()
+}
+
+#[test]
+fn rank_sin_tildes_y_con_erratas() {
+    let vivienda = |id: &str, calle: &str| TipoVivienda {
+        calle: calle.to_string(),
+        tipo: crate::entidad::Tipo::Chalet,
+        municipio: String::from("Ciempozuelos"),
+        provincia: String::from("Madrid"),
+        ..TipoVivienda::example(id)
+    };
+    assert_eq!(words("C/ José-Antonio, 4"), vec!["c", "jose", "antonio", "4"]);
+    assert_eq!(distance("jsoe", "jose"), 1);
+    assert_eq!(distance("antnio", "antonio"), 1);
+
+    let datos = vec![
+        vivienda("1", "Calle Josefa Valcárcel"),
+        vivienda("2", "Avenida José Antonio"),
+        vivienda("3", "Calle San Isidro"),
+    ];
+    assert_eq!(rank(&datos, "Jose Antonio"), vec![1]);
+    assert_eq!(rank(&datos, "jsoe antnio"), vec![1]);
+    // Igual puntúa más que principio de palabra
+    assert_eq!(rank(&datos, "jose"), vec![1, 0]);
+    assert_eq!(rank(&datos, "isidro chalet 28350"), vec![2]);
+    // En el código postal no hay erratas que valgan
+    assert!(rank(&datos, "28530").is_empty());
+    assert_eq!(rank(&datos, "").len(), 3);
+}
@@ -0,0 +1,461 @@
+use std::{collections::HashMap, error::Error, path::Path, str::FromStr};
+
+use crate::entidad::{parse_fecha, parse_precio, today, Estado, Tipo, TipoVivienda, TipoViviendaDAO, MONEDA_POR_DEFECTO};
+use crate::informe::{self, Agrupacion};
+use crate::estadisticas::Estadisticas;
+use crate::direccion::Direccion;
+use crate::duplicados;
+use crate::versiones::{self, Version};
+use crate::codigos_postales::CatalogoCodigosPostales;
+use crate::configuracion::Configuracion;
+
+const USO: &str = "Uso: AppGUI [--db <fichero.sqlite>] [--json] <orden> [argumentos]
+
+Órdenes:
+  list                          Lista todas las viviendas
+  show <id>                     Muestra una vivienda
+  search <texto>...             Busca en cualquier campo sin distinguir tildes y con erratas en la calle
+  add <id> --calle <c> --numero <n> --codigo-postal <cp> --metros <m> --tipo <t>
+          [--piso <p>] [--aseos <a>] [--habitaciones <h>] [--precio <importe>] [--moneda <m>]
+          [--estado Disponible|Reservado|Vendido|Alquilado] [--fecha-alta <AAAA-MM-DD>] [--descripcion <d>]
+          [--municipio <m>] [--provincia <p>]
+  update <id> [--calle <c>] [--numero <n>] [--piso <p>] [--codigo-postal <cp>]
+          [--metros <m>] [--aseos <a>] [--habitaciones <h>] [--tipo <t>] [--precio <importe>]
+          [--moneda <m>] [--estado <e>] [--fecha-alta <AAAA-MM-DD>] [--descripcion <d>]
+          [--municipio <m>] [--provincia <p>]
+  delete <id>...                Mueve las viviendas a la papelera
+  normalize [<id>...]           Normaliza las direcciones y completa municipio y provincia
+  history <id>                  Operaciones registradas sobre una vivienda
+  versions <id>                 Versiones de una vivienda, de la más antigua a la más reciente
+  diff <id> <version> <version> Diferencias campo a campo entre dos versiones
+  revert <id> <version>         Restaura una versión anterior como la actual
+  duplicates                    Lista las viviendas con la misma dirección
+  merge <conservar> <id>...     Fusiona duplicados: completa la primera y manda las demás a la papelera
+  import <fichero>              Añade o reemplaza viviendas desde .csv, .json o .jsonl
+  export <fichero>              Exporta todas las viviendas a .csv, .json, .jsonl, .xlsx u .ods
+  backup                        Hace una copia de seguridad de la base de datos
+  backups                       Lista las copias de seguridad, de la más reciente a la más antigua
+  restore <n|fichero>           Restaura la copia n de la lista de backups o el fichero indicado
+  stats                         Estadísticas del catálogo por tipo y código postal
+  report <fichero.pdf> [--agrupar tipo|codigo-postal]
+                                Catálogo en PDF agrupado por tipo o código postal
+  report <fichero.pdf> --id <id>
+                                Ficha en PDF de una vivienda
+
+Sin argumentos se abre la interfaz gráfica.";
+
+const CAMPOS_OBLIGATORIOS: [&str; 5] = ["calle", "numero", "codigo-postal", "metros", "tipo"];
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Argumentos {
+    pub db: Option<String>,
+    pub json: bool,
+    pub posicionales: Vec<String>,
+    pub opciones: HashMap<String, String>,
+}
+
+impl Argumentos {
+    pub fn parse(args: &[String]) -> Result<Argumentos, Box<dyn Error>> {
+        let mut argumentos = Argumentos::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.strip_prefix("--") {
+                Some("json") => argumentos.json = true,
+                Some(nombre) => {
+                    let valor = iter.next().ok_or_else(|| format!("Falta el valor de --{}", nombre))?;
+                    if nombre == "db" {
+                        argumentos.db = Some(valor.clone());
+                    } else {
+                        argumentos.opciones.insert(nombre.to_string(), valor.clone());
+                    }
+                }
+                None => argumentos.posicionales.push(arg.clone()),
+            }
+        }
+        Ok(argumentos)
+    }
+}
+
+pub fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let argumentos = Argumentos::parse(args)?;
+    let orden = match argumentos.posicionales.first() {
+        Some(orden) => orden.as_str(),
+        None => {
+            println!("{}", USO);
+            return Ok(());
+        }
+    };
+    if orden == "help" {
+        println!("{}", USO);
+        return Ok(());
+    }
+
+    let mut tipoViviendaDAO = match &argumentos.db {
+        Some(path) => TipoViviendaDAO::open(path)?,
+        None => TipoViviendaDAO::try_new().map_err(|e| format!("{}\n{}", e, USO))?,
+    };
+    tipoViviendaDAO.enable_backups(Configuracion::load().copias);
+    let mut datos = tipoViviendaDAO.asVector();
+    datos.sort_by(|a, b| a.identificacion.cmp(&b.identificacion));
+
+    match orden {
+        "list" => print_list(&datos, argumentos.json)?,
+        "show" => {
+            let id = argument(&argumentos, 1, "<id>")?;
+            let tipo_vivienda = datos.iter().find(|e| e.identificacion == id)
+                .ok_or_else(|| format!("No existe la vivienda {}", id))?;
+            print_list(&vec![tipo_vivienda.clone()], argumentos.json)?;
+        }
+        "search" => {
+            let texto = argumentos.posicionales[1..].join(" ");
+            if texto.trim().is_empty() {
+                return Err("Falta <texto>".into());
+            }
+            print_list(&tipoViviendaDAO.search(&texto)?, argumentos.json)?;
+        }
+        "add" => {
+            let id = argument(&argumentos, 1, "<id>")?;
+            if datos.iter().any(|e| e.identificacion == id) {
+                return Err(format!("Ya existe la vivienda {}", id).into());
+            }
+            for campo in CAMPOS_OBLIGATORIOS {
+                if !argumentos.opciones.contains_key(campo) {
+                    return Err(format!("Falta --{}", campo).into());
+                }
+            }
+            let mut tipo_vivienda = TipoVivienda {
+                identificacion: id.to_string(),
+                calle: String::new(),
+                numero: 0,
+                piso: String::new(),
+                codigo_postal: String::new(),
+                metros_cuadrados: 0,
+                numero_aseos: 0,
+                numero_habitaciones: 0,
+                tipo: Tipo::Apartamento,
+                precio: 0.0,
+                moneda: String::from(MONEDA_POR_DEFECTO),
+                estado: Estado::Disponible,
+                fecha_alta: today(),
+                descripcion: String::new(),
+                municipio: String::new(),
+                provincia: String::new()
+            };
+            apply(&mut tipo_vivienda, &argumentos.opciones)?;
+            check_codigo_postal(&mut tipo_vivienda)?;
+            warn_duplicates(&tipo_vivienda, &datos);
+            datos.push(tipo_vivienda);
+            println!("{}", tipoViviendaDAO.save(&datos)?);
+        }
+        "update" => {
+            let id = argument(&argumentos, 1, "<id>")?;
+            let tipo_vivienda = datos.iter_mut().find(|e| e.identificacion == id)
+                .ok_or_else(|| format!("No existe la vivienda {}", id))?;
+            apply(tipo_vivienda, &argumentos.opciones)?;
+            check_codigo_postal(tipo_vivienda)?;
+            println!("{}", tipoViviendaDAO.save(&datos)?);
+        }
+        "delete" => {
+            let ids = &argumentos.posicionales[1..];
+            if ids.is_empty() {
+                return Err("Falta <id>".into());
+            }
+            for id in ids {
+                if !datos.iter().any(|e| &e.identificacion == id) {
+                    return Err(format!("No existe la vivienda {}", id).into());
+                }
+            }
+            datos.retain(|e| !ids.contains(&e.identificacion));
+            println!("{}", tipoViviendaDAO.save(&datos)?);
+        }
+        "import" => {
+            let path = argument(&argumentos, 1, "<fichero>")?;
+            for tipo_vivienda in tipoViviendaDAO.import(Path::new(path))? {
+                datos.retain(|e| e.identificacion != tipo_vivienda.identificacion);
+                warn_duplicates(&tipo_vivienda, &datos);
+                datos.push(tipo_vivienda);
+            }
+            println!("{}", tipoViviendaDAO.save(&datos)?);
+        }
+        "normalize" => {
+            let ids = &argumentos.posicionales[1..];
+            let catalogo = CatalogoCodigosPostales::load(Configuracion::load().codigos_postales.as_deref())?;
+            for tipo_vivienda in datos.iter_mut().filter(|e| ids.is_empty() || ids.contains(&e.identificacion)) {
+                Direccion::from_tipo_vivienda(tipo_vivienda).normalize().apply(tipo_vivienda);
+                catalogo.complete(tipo_vivienda);
+                println!("{}: {}", tipo_vivienda.identificacion, Direccion::from_tipo_vivienda(tipo_vivienda));
+                for aviso in catalogo.check(tipo_vivienda) {
+                    eprintln!("  Aviso: {}", aviso);
+                }
+            }
+            println!("{}", tipoViviendaDAO.save(&datos)?);
+        }
+        "history" => {
+            let id = argument(&argumentos, 1, "<id>")?;
+            let historial = tipoViviendaDAO.history(id)?;
+            if argumentos.json {
+                println!("{}", serde_json::to_string_pretty(&historial)?);
+            } else {
+                for registro in &historial {
+                    println!("{}  {}  {}", registro.fecha, registro.operacion, registro.usuario);
+                    for (campo, antes, despues) in registro.changes() {
+                        println!("  {}: '{}' -> '{}'", campo, antes, despues);
+                    }
+                }
+            }
+        }
+        "versions" => {
+            let id = argument(&argumentos, 1, "<id>")?;
+            let versiones = tipoViviendaDAO.versions(id)?;
+            if argumentos.json {
+                println!("{}", serde_json::to_string_pretty(&versiones)?);
+            } else {
+                for version in &versiones {
+                    println!("v{}  {}  {}  {}", version.numero, version.fecha, version.operacion, version.usuario);
+                }
+            }
+        }
+        "diff" => {
+            let id = argument(&argumentos, 1, "<id>")?;
+            let versiones = tipoViviendaDAO.versions(id)?;
+            let antes = find_version(&versiones, argument(&argumentos, 2, "<version>")?)?;
+            let despues = find_version(&versiones, argument(&argumentos, 3, "<version>")?)?;
+            for (campo, valor_antes, valor_despues) in versiones::diff_versions(antes, despues) {
+                println!("{}: '{}' -> '{}'", campo, valor_antes, valor_despues);
+            }
+        }
+        "revert" => {
+            let id = argument(&argumentos, 1, "<id>")?;
+            let versiones = tipoViviendaDAO.versions(id)?;
+            let version = find_version(&versiones, argument(&argumentos, 2, "<version>")?)?;
+            let tipo_vivienda = tipoViviendaDAO.restore_version(version)?;
+            println!("Restaurada la versión v{} de {}", version.numero, tipo_vivienda.identificacion);
+        }
+        "duplicates" => {
+            let grupos: Vec<Vec<TipoVivienda>> = duplicados::groups(&datos).iter()
+                .map(|grupo| grupo.iter().map(|i| datos[*i].clone()).collect())
+                .collect();
+            if argumentos.json {
+                println!("{}", serde_json::to_string_pretty(&grupos)?);
+            } else {
+                for grupo in &grupos {
+                    println!("{}", Direccion::from_tipo_vivienda(&grupo[0]).normalize());
+                    let ids: Vec<&str> = grupo.iter().map(|e| e.identificacion.as_str()).collect();
+                    println!("  {}", ids.join(", "));
+                }
+                println!("{} grupos de posibles duplicados", grupos.len());
+            }
+        }
+        "merge" => {
+            let id = argument(&argumentos, 1, "<conservar>")?;
+            let ids = &argumentos.posicionales[2..];
+            if ids.is_empty() {
+                return Err("Falta <id>".into());
+            }
+            let conservada = datos.iter().find(|e| e.identificacion == id)
+                .ok_or_else(|| format!("No existe la vivienda {}", id))?;
+            let mut otras = Vec::new();
+            for otra in ids {
+                if otra == id {
+                    return Err(format!("La vivienda {} no puede fusionarse consigo misma", id).into());
+                }
+                otras.push(datos.iter().find(|e| &e.identificacion == otra)
+                    .ok_or_else(|| format!("No existe la vivienda {}", otra))?.clone());
+            }
+            let fusionada = duplicados::merge(conservada, &otras);
+            let borradas = tipoViviendaDAO.merge(&fusionada, ids)?;
+            println!("Fusionadas en {}: {} viviendas a la papelera", id, borradas);
+        }
+        "export" => {
+            let path = argument(&argumentos, 1, "<fichero>")?;
+            tipoViviendaDAO.export(Path::new(path), &datos)?;
+            println!("Exportadas {} viviendas a {}", datos.len(), path);
+        }
+        "backup" => println!("Copia de seguridad en {}", tipoViviendaDAO.backup()?.display()),
+        "backups" => {
+            let lista = tipoViviendaDAO.backups()?;
+            let filas: Vec<Vec<String>> = lista.iter().enumerate()
+                .map(|(i, copia)| vec![
+                    (i + 1).to_string(),
+                    copia.fecha.clone(),
+                    copia.registros.map(|registros| registros.to_string()).unwrap_or_else(|| String::from("?")),
+                    copia.path.display().to_string(),
+                ])
+                .collect();
+            print!("{}", table(&["N", "Fecha", "Viviendas", "Fichero"], &filas));
+        }
+        "restore" => {
+            let copia = argument(&argumentos, 1, "<n|fichero>")?;
+            let path = match copia.parse::<usize>() {
+                Ok(n) => tipoViviendaDAO.backups()?.get(n.wrapping_sub(1)).map(|copia| copia.path.clone())
+                    .ok_or_else(|| format!("No existe la copia {}", n))?,
+                Err(_) => std::path::PathBuf::from(copia),
+            };
+            tipoViviendaDAO.restore_backup(&path)?;
+            println!("Restaurada la copia {} ({} viviendas)", path.display(), tipoViviendaDAO.asVector().len());
+        }
+        "stats" => print_stats(&tipoViviendaDAO.statistics()?, argumentos.json)?,
+        "report" => {
+            let path = Path::new(argument(&argumentos, 1, "<fichero.pdf>")?);
+            match argumentos.opciones.get("id") {
+                Some(id) => {
+                    let tipo_vivienda = datos.iter().find(|e| &e.identificacion == id)
+                        .ok_or_else(|| format!("No existe la vivienda {}", id))?;
+                    informe::fact_sheet(path, tipo_vivienda)?;
+                }
+                None => {
+                    let agrupacion = match argumentos.opciones.get("agrupar").map(|s| s.as_str()) {
+                        None | Some("tipo") => Agrupacion::Tipo,
+                        Some("codigo-postal") => Agrupacion::CodigoPostal,
+                        Some(otra) => return Err(format!("Agrupación desconocida: {}", otra).into()),
+                    };
+                    informe::catalogue(path, &datos, agrupacion)?;
+                }
+            }
+            println!("Informe generado en {}", path.display());
+        }
+        otra => return Err(format!("Orden desconocida: {}\n\n{}", otra, USO).into()),
+    }
+    Ok(())
+}
+
+// Completa municipio y provincia; un código mal formado es un error y el resto se avisa por stderr
+fn check_codigo_postal(tipo_vivienda: &mut TipoVivienda) -> Result<(), Box<dyn Error>> {
+    let catalogo = CatalogoCodigosPostales::load(Configuracion::load().codigos_postales.as_deref())?;
+    catalogo.complete(tipo_vivienda);
+    for aviso in catalogo.check(tipo_vivienda) {
+        if aviso.is_error() {
+            return Err(aviso.to_string().into());
+        }
+        eprintln!("Aviso: {}", aviso);
+    }
+    Ok(())
+}
+
+// Acepta "3" o "v3"
+fn find_version<'a>(versiones: &'a [Version], numero: &str) -> Result<&'a Version, Box<dyn Error>> {
+    let numero: usize = numero.trim_start_matches('v').parse().map_err(|_| format!("Versión no válida: {}", numero))?;
+    versiones.iter().find(|version| version.numero == numero).ok_or_else(|| format!("No existe la versión v{}", numero).into())
+}
+
+fn warn_duplicates(tipo_vivienda: &TipoVivienda, datos: &[TipoVivienda]) {
+    let otras: Vec<&str> = duplicados::find_matches(tipo_vivienda, datos).iter().map(|e| e.identificacion.as_str()).collect();
+    if !otras.is_empty() {
+        eprintln!("Aviso: {} tiene la misma dirección que {}", tipo_vivienda.identificacion, otras.join(", "));
+    }
+}
+
+fn argument<'a>(argumentos: &'a Argumentos, posicion: usize, nombre: &str) -> Result<&'a str, Box<dyn Error>> {
+    argumentos.posicionales.get(posicion)
+        .map(|valor| valor.as_str())
+        .ok_or_else(|| format!("Falta {}", nombre).into())
+}
+
+pub fn apply(tipo_vivienda: &mut TipoVivienda, opciones: &HashMap<String, String>) -> Result<(), Box<dyn Error>> {
+    let entero = |campo: &str, valor: &str| -> Result<i32, Box<dyn Error>> {
+        valor.trim().parse::<i32>().map_err(|_| format!("--{} no es un número: '{}'", campo, valor).into())
+    };
+    for (campo, valor) in opciones {
+        match campo.as_str() {
+            "calle" => tipo_vivienda.calle = valor.clone(),
+            "numero" => tipo_vivienda.numero = entero(campo, valor)?,
+            "piso" => tipo_vivienda.piso = valor.clone(),
+            "codigo-postal" => tipo_vivienda.codigo_postal = valor.clone(),
+            "metros" => tipo_vivienda.metros_cuadrados = entero(campo, valor)?,
+            "aseos" => tipo_vivienda.numero_aseos = entero(campo, valor)?,
+            "habitaciones" => tipo_vivienda.numero_habitaciones = entero(campo, valor)?,
+            "tipo" => tipo_vivienda.tipo = Tipo::from_str(valor).map_err(|_| format!("Tipo desconocido: '{}'", valor))?,
+            "precio" => tipo_vivienda.precio = parse_precio(valor)?,
+            "moneda" => tipo_vivienda.moneda = valor.trim().to_uppercase(),
+            "estado" => tipo_vivienda.estado = Estado::from_str(valor).map_err(|_| format!("Estado desconocido: '{}'", valor))?,
+            "fecha-alta" => tipo_vivienda.fecha_alta = parse_fecha(valor)?,
+            "descripcion" => tipo_vivienda.descripcion = valor.clone(),
+            "municipio" => tipo_vivienda.municipio = valor.clone(),
+            "provincia" => tipo_vivienda.provincia = valor.clone(),
+            otro => return Err(format!("Opción desconocida: --{}", otro).into()),
+        }
+    }
+    Ok(())
+}
+
+fn print_list(datos: &Vec<TipoVivienda>, json: bool) -> Result<(), Box<dyn Error>> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(datos)?);
+        return Ok(());
+    }
+    let cabecera = vec!["Id", "Calle", "Nº", "Piso", "C.P.", "m²", "Aseos", "Hab.", "Tipo", "Precio", "Estado", "Alta"];
+    let filas: Vec<Vec<String>> = datos.iter().map(|e| vec![
+        e.identificacion.clone(),
+        e.calle.clone(),
+        e.numero.to_string(),
+        e.piso.clone(),
+        e.codigo_postal.clone(),
+        e.metros_cuadrados.to_string(),
+        e.numero_aseos.to_string(),
+        e.numero_habitaciones.to_string(),
+        e.tipo.to_string(),
+        format!("{:.2} {}", e.precio, e.moneda),
+        e.estado.to_string(),
+        e.fecha_alta.clone(),
+    ]).collect();
+    print!("{}", table(&cabecera, &filas));
+    Ok(())
+}
+
+fn print_stats(estadisticas: &Estadisticas, json: bool) -> Result<(), Box<dyn Error>> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(estadisticas)?);
+    } else {
+        for linea in estadisticas.lines() {
+            println!("{}", linea);
+        }
+    }
+    Ok(())
+}
+
+pub fn table(cabecera: &[&str], filas: &[Vec<String>]) -> String {
+    let mut anchos: Vec<usize> = cabecera.iter().map(|c| c.chars().count()).collect();
+    for fila in filas {
+        for (i, celda) in fila.iter().enumerate() {
+            anchos[i] = anchos[i].max(celda.chars().count());
+        }
+    }
+    let linea = |celdas: Vec<&str>| -> String {
+        let texto: Vec<String> = celdas.iter().enumerate()
+            .map(|(i, celda)| format!("{}{}", celda, " ".repeat(anchos[i] - celda.chars().count())))
+            .collect();
+        format!("{}\n", texto.join("  ").trim_end())
+    };
+    let mut salida = linea(cabecera.to_vec());
+    let guiones: Vec<String> = anchos.iter().map(|ancho| "-".repeat(*ancho)).collect();
+    salida.push_str(&linea(guiones.iter().map(|s| s.as_str()).collect()));
+    for fila in filas {
+        salida.push_str(&linea(fila.iter().map(|s| s.as_str()).collect()));
+    }
+    salida
+}
+
+#[test]
+fn parse_argumentos() {
+    let args: Vec<String> = vec!["--db", "otra.sqlite", "update", "1", "--calle", "Chile", "--json"]
+        .into_iter().map(String::from).collect();
+    let argumentos = Argumentos::parse(&args).unwrap();
+    assert_eq!(argumentos.db, Some(String::from("otra.sqlite")));
+    assert!(argumentos.json);
+    assert_eq!(argumentos.posicionales, vec!["update", "1"]);
+    assert_eq!(argumentos.opciones.get("calle"), Some(&String::from("Chile")));
+}
+
+#[test]
+fn apply_opciones() {
+    use crate::entidad::ScreenOutput;
+    let mut tipo_vivienda = TipoVivienda::example("1");
+    let mut opciones = HashMap::new();
+    opciones.insert(String::from("metros"), String::from("95"));
+    opciones.insert(String::from("tipo"), String::from("Casa"));
+    apply(&mut tipo_vivienda, &opciones).unwrap();
+    assert_eq!(tipo_vivienda.toScreen(), "\"1\",\"San Isidro\",4,\"1C\",\"28350\",95,1,2,Casa");
+
+    opciones.insert(String::from("numero"), String::from("cuatro"));
+    assert!(apply(&mut tipo_vivienda, &opciones).is_err());
+}
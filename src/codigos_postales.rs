@@ -0,0 +1,157 @@
+use std::{error::Error, fmt, path::Path};
+use csv::Reader;
+use serde::Deserialize;
+
+use crate::direccion::{self, fold};
+use crate::entidad::TipoVivienda;
+
+// Datos incluidos en el ejecutable: rangos de las capitales y de algunos municipios.
+// Un fichero local con el mismo formato los sustituye sin recompilar.
+const DATOS: &str = include_str!("csv/codigos-postales.csv");
+pub const PATH_LOCAL: &str = "./codigos-postales.csv";
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct Entrada {
+    pub desde: String,
+    pub hasta: String,
+    pub municipio: String,
+    pub provincia: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Aviso {
+    Formato(String),
+    Desconocido(String),
+    Municipio { escrito: String, esperado: Vec<String> },
+    Provincia { escrita: String, esperada: String },
+}
+
+impl Aviso {
+    // Solo un código mal formado impide guardar; el resto son advertencias
+    pub fn is_error(&self) -> bool {
+        matches!(self, Aviso::Formato(_))
+    }
+}
+
+impl fmt::Display for Aviso {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Aviso::Formato(codigo_postal) => write!(f, "Código postal no válido: '{}'", codigo_postal),
+            Aviso::Desconocido(codigo_postal) => write!(f, "El código postal {} no está en el catálogo", codigo_postal),
+            Aviso::Municipio { escrito, esperado } => write!(f, "El municipio '{}' no corresponde al código postal ({})", escrito, esperado.join(", ")),
+            Aviso::Provincia { escrita, esperada } => write!(f, "La provincia '{}' no corresponde al código postal ({})", escrita, esperada),
+        }
+    }
+}
+
+// Cinco dígitos con un prefijo de provincia existente
+pub fn check_format(codigo_postal: &str) -> Result<(), Aviso> {
+    match direccion::provincia(codigo_postal) {
+        Some(_) => Ok(()),
+        None => Err(Aviso::Formato(codigo_postal.to_string())),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CatalogoCodigosPostales {
+    entradas: Vec<Entrada>,
+}
+
+impl CatalogoCodigosPostales {
+
+    pub fn bundled() -> CatalogoCodigosPostales {
+        CatalogoCodigosPostales::parse(DATOS.as_bytes()).expect("Catálogo de códigos postales incluido no válido")
+    }
+
+    pub fn from_path(path: &Path) -> Result<CatalogoCodigosPostales, Box<dyn Error>> {
+        CatalogoCodigosPostales::parse(&std::fs::read(path)?)
+    }
+
+    fn parse(bytes: &[u8]) -> Result<CatalogoCodigosPostales, Box<dyn Error>> {
+        let mut entradas = Vec::new();
+        for result in Reader::from_reader(bytes).deserialize() {
+            let entrada: Entrada = result?;
+            check_format(&entrada.desde).map_err(|aviso| aviso.to_string())?;
+            check_format(&entrada.hasta).map_err(|aviso| aviso.to_string())?;
+            entradas.push(entrada);
+        }
+        Ok(CatalogoCodigosPostales { entradas })
+    }
+
+    // El fichero indicado en la configuración, si no ./codigos-postales.csv y si no el incluido
+    pub fn load(path: Option<&str>) -> Result<CatalogoCodigosPostales, Box<dyn Error>> {
+        let path = path.unwrap_or(PATH_LOCAL);
+        if !Path::new(path).exists() {
+            return Ok(CatalogoCodigosPostales::bundled());
+        }
+        CatalogoCodigosPostales::from_path(Path::new(path))
+            .map_err(|e| format!("No se pudo leer el catálogo de códigos postales {}: {}", path, e).into())
+    }
+
+    // Un código puede compartirse entre varios municipios
+    pub fn find(&self, codigo_postal: &str) -> Vec<&Entrada> {
+        let codigo_postal = codigo_postal.trim();
+        self.entradas.iter()
+            .filter(|entrada| entrada.desde.as_str() <= codigo_postal && codigo_postal <= entrada.hasta.as_str())
+            .collect()
+    }
+
+    pub fn check(&self, p: &TipoVivienda) -> Vec<Aviso> {
+        if let Err(aviso) = check_format(&p.codigo_postal) {
+            return vec![aviso];
+        }
+        let mut avisos = Vec::new();
+        let entradas = self.find(&p.codigo_postal);
+        if entradas.is_empty() {
+            avisos.push(Aviso::Desconocido(p.codigo_postal.clone()));
+        } else if !p.municipio.is_empty() && !entradas.iter().any(|entrada| fold(&entrada.municipio) == fold(&p.municipio)) {
+            avisos.push(Aviso::Municipio {
+                escrito: p.municipio.clone(),
+                esperado: entradas.iter().map(|entrada| entrada.municipio.clone()).collect(),
+            });
+        }
+        let esperada = direccion::provincia(&p.codigo_postal).unwrap_or_default();
+        if !p.provincia.is_empty() && fold(&p.provincia) != fold(esperada) {
+            avisos.push(Aviso::Provincia { escrita: p.provincia.clone(), esperada: esperada.to_string() });
+        }
+        avisos
+    }
+
+    // Rellena municipio y provincia vacíos; el municipio solo si el código es de uno solo
+    pub fn complete(&self, p: &mut TipoVivienda) -> bool {
+        let antes = (p.municipio.clone(), p.provincia.clone());
+        if p.municipio.trim().is_empty() {
+            if let [entrada] = self.find(&p.codigo_postal).as_slice() {
+                p.municipio = entrada.municipio.clone();
+            }
+        }
+        direccion::complete_provincia(p);
+        antes != (p.municipio.clone(), p.provincia.clone())
+    }
+}
+
+#[test]
+fn check_codigos_postales() {
+    let catalogo = CatalogoCodigosPostales::bundled();
+    let mut tipo_vivienda = TipoVivienda {
+        calle: String::from("C/ San Isidro"),
+        piso: String::from("1º C"),
+        ..TipoVivienda::example("1")
+    };
+    assert!(catalogo.complete(&mut tipo_vivienda));
+    assert_eq!((tipo_vivienda.municipio.as_str(), tipo_vivienda.provincia.as_str()), ("Ciempozuelos", "Madrid"));
+    assert!(catalogo.check(&tipo_vivienda).is_empty());
+
+    tipo_vivienda.municipio = String::from("Getafe");
+    tipo_vivienda.provincia = String::from("Toledo");
+    assert_eq!(catalogo.check(&tipo_vivienda).len(), 2);
+
+    tipo_vivienda.codigo_postal = String::from("2835");
+    assert!(catalogo.check(&tipo_vivienda)[0].is_error());
+    tipo_vivienda.codigo_postal = String::from("28399");
+    tipo_vivienda.provincia = String::from("Madrid");
+    // Un código que no está en el catálogo se avisa pero no impide guardar
+    let avisos = catalogo.check(&tipo_vivienda);
+    assert_eq!(avisos, vec![Aviso::Desconocido(String::from("28399"))]);
+    assert!(!avisos[0].is_error());
+}
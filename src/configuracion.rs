@@ -0,0 +1,69 @@
+use std::{fs, error::Error};
+use serde::{Deserialize, Serialize};
+
+const PATH_CONFIGURACION: &str = "./configuracion.json";
+const MAX_RECIENTES: usize = 8;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Configuracion {
+    pub recientes: Vec<String>,
+    pub ventana: Option<Ventana>,
+    // Catálogo de códigos postales alternativo al incluido en la aplicación
+    pub codigos_postales: Option<String>,
+    pub copias: CopiasSeguridad,
+}
+
+// Copias de seguridad de la base de datos: antes de cada guardado y cada intervalo_minutos (0 las desactiva)
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CopiasSeguridad {
+    pub directorio: String,
+    pub retencion: usize,
+    pub intervalo_minutos: u32,
+}
+
+impl Default for CopiasSeguridad {
+    fn default() -> Self {
+        CopiasSeguridad { directorio: String::from("./copias"), retencion: 10, intervalo_minutos: 30 }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+pub struct Ventana {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+impl Configuracion {
+
+    // Si el fichero no existe o no se puede leer se parte de la configuración por defecto
+    pub fn load() -> Configuracion {
+        fs::read_to_string(PATH_CONFIGURACION)
+            .ok()
+            .and_then(|texto| serde_json::from_str(&texto).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        fs::write(PATH_CONFIGURACION, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn add_reciente(&mut self, path: &str) {
+        self.recientes.retain(|p| p != path);
+        self.recientes.insert(0, path.to_string());
+        self.recientes.truncate(MAX_RECIENTES);
+    }
+}
+
+#[test]
+fn add_reciente_configuracion() {
+    let mut configuracion = Configuracion::default();
+    configuracion.add_reciente("a.sqlite");
+    configuracion.add_reciente("b.sqlite");
+    configuracion.add_reciente("a.sqlite");
+    assert_eq!(configuracion.recientes, vec!["a.sqlite", "b.sqlite"]);
+}
@@ -0,0 +1,150 @@
+use std::{error::Error, fs::{self, OpenOptions}, io::ErrorKind, path::{Path, PathBuf}};
+use chrono::{DateTime, Local};
+use rusqlite::{backup::Progress, Connection, DatabaseName};
+
+use crate::configuracion::CopiasSeguridad;
+
+#[derive(Debug, Clone)]
+pub struct Copia {
+    pub path: PathBuf,
+    pub fecha: String,
+    // None si la copia no se puede leer
+    pub registros: Option<i64>,
+}
+
+// DATABASE_URL puede llevar el prefijo sqlite://, que rusqlite no entiende
+fn database_path(database_url: &str) -> &str {
+    database_url.strip_prefix("sqlite://").unwrap_or(database_url)
+}
+
+fn stem(database_url: &str) -> String {
+    Path::new(database_path(database_url)).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+// curso.sqlite -> curso-20261019-101500123.sqlite, y curso-20261019-101500123-1.sqlite si ya había
+// otra copia en ese milisegundo
+fn name(database_url: &str, marca: &str, n: u32) -> String {
+    match n {
+        0 => format!("{}-{}.sqlite", stem(database_url), marca),
+        n => format!("{}-{}-{}.sqlite", stem(database_url), marca, n),
+    }
+}
+
+// Fecha y contador del nombre de una copia de database_url, para ordenarlas; None si es otro fichero,
+// aunque empiece igual: curso-test-20261019-101500123.sqlite no es una copia de curso.sqlite
+fn stamp(nombre: &str, database_url: &str) -> Option<(String, u32)> {
+    let resto = nombre.strip_prefix(&stem(database_url))?.strip_prefix('-')?.strip_suffix(".sqlite")?;
+    let digitos = |parte: &str, n: usize| parte.len() == n && parte.bytes().all(|b| b.is_ascii_digit());
+    match resto.split('-').collect::<Vec<&str>>().as_slice() {
+        [dia, hora] if digitos(dia, 8) && digitos(hora, 9) => Some((resto.to_string(), 0)),
+        [dia, hora, n] if digitos(dia, 8) && digitos(hora, 9) && digitos(n, n.len()) => Some((format!("{}-{}", dia, hora), n.parse().ok()?)),
+        _ => None,
+    }
+}
+
+fn count(path: &Path) -> Result<i64, rusqlite::Error> {
+    Connection::open(path)?.query_row("SELECT COUNT(*) FROM tipo_viviendas WHERE deleted_at IS NULL", [], |fila| fila.get(0))
+}
+
+// Copia en caliente con la API de backup de SQLite
+fn copy(database_url: &str, copias: &CopiasSeguridad) -> Result<PathBuf, Box<dyn Error>> {
+    if database_path(database_url) == ":memory:" {
+        return Err("No se pueden hacer copias de una base de datos en memoria".into());
+    }
+    fs::create_dir_all(&copias.directorio)?;
+    // create_new reserva el nombre de forma atómica: dos copias en el mismo milisegundo, aunque sean
+    // de procesos distintos, no se pisan y la segunda lleva contador
+    let marca = Local::now().format("%Y%m%d-%H%M%S%3f").to_string();
+    let mut n = 0;
+    let destino = loop {
+        let destino = Path::new(&copias.directorio).join(name(database_url, &marca, n));
+        match OpenOptions::new().write(true).create_new(true).open(&destino) {
+            Ok(_) => break destino,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => n += 1,
+            Err(e) => return Err(e.into()),
+        }
+    };
+    if let Err(e) = Connection::open(database_path(database_url)).and_then(|conn| conn.backup(DatabaseName::Main, &destino, None)) {
+        fs::remove_file(&destino)?;
+        return Err(e.into());
+    }
+    Ok(destino)
+}
+
+// Copia y borra las que sobran según la retención
+pub fn backup(database_url: &str, copias: &CopiasSeguridad) -> Result<PathBuf, Box<dyn Error>> {
+    let destino = copy(database_url, copias)?;
+    rotate(database_url, copias)?;
+    Ok(destino)
+}
+
+// Copias de la base de datos, de la más reciente a la más antigua
+pub fn list(database_url: &str, copias: &CopiasSeguridad) -> Result<Vec<Copia>, Box<dyn Error>> {
+    let directorio = Path::new(&copias.directorio);
+    if !directorio.exists() {
+        return Ok(Vec::new());
+    }
+    let mut paths: Vec<((String, u32), PathBuf)> = fs::read_dir(directorio)?
+        .filter_map(|entrada| entrada.ok().map(|entrada| entrada.path()))
+        .filter_map(|path| Some((stamp(&path.file_name()?.to_string_lossy(), database_url)?, path)))
+        .collect();
+    paths.sort();
+    paths.reverse();
+    let mut lista = Vec::new();
+    for (_, path) in paths {
+        let fecha: DateTime<Local> = fs::metadata(&path)?.modified()?.into();
+        lista.push(Copia { fecha: fecha.format("%Y-%m-%d %H:%M:%S").to_string(), registros: count(&path).ok(), path });
+    }
+    Ok(lista)
+}
+
+fn rotate(database_url: &str, copias: &CopiasSeguridad) -> Result<usize, Box<dyn Error>> {
+    let sobrantes: Vec<Copia> = list(database_url, copias)?.into_iter().skip(copias.retencion.max(1)).collect();
+    for copia in &sobrantes {
+        fs::remove_file(&copia.path)?;
+    }
+    Ok(sobrantes.len())
+}
+
+// Antes de sobrescribir la base de datos se copia su estado actual, por si hay que deshacer;
+// la rotación va al final para no borrar la copia que se está restaurando
+pub fn restore(database_url: &str, copia: &Path, copias: &CopiasSeguridad) -> Result<(), Box<dyn Error>> {
+    count(copia).map_err(|e| format!("{} no es una copia válida: {}", copia.display(), e))?;
+    copy(database_url, copias)?;
+    Connection::open(database_path(database_url))?.restore(DatabaseName::Main, copia, None::<fn(Progress)>)?;
+    rotate(database_url, copias)?;
+    Ok(())
+}
+
+#[test]
+fn backup_rotate_y_restore() {
+    let directorio = std::env::temp_dir().join(format!("copias-{}", std::process::id()));
+    let database_url = directorio.join("prueba.sqlite").to_string_lossy().to_string();
+    let copias = CopiasSeguridad { directorio: directorio.join("copias").to_string_lossy().to_string(), retencion: 2, intervalo_minutos: 0 };
+    fs::create_dir_all(&directorio).unwrap();
+    let conn = Connection::open(&database_url).unwrap();
+    conn.execute_batch("CREATE TABLE tipo_viviendas (identificacion TEXT, deleted_at TEXT); INSERT INTO tipo_viviendas VALUES ('1', NULL);").unwrap();
+
+    let primera = backup(&database_url, &copias).unwrap();
+    conn.execute("INSERT INTO tipo_viviendas VALUES ('2', NULL)", []).unwrap();
+    backup(&database_url, &copias).unwrap();
+    backup(&database_url, &copias).unwrap();
+    // La rotación no toca las copias de otra base de datos con un nombre que empieza igual
+    let otra_url = directorio.join("prueba-test.sqlite").to_string_lossy().to_string();
+    Connection::open(&otra_url).unwrap().execute_batch("CREATE TABLE tipo_viviendas (identificacion TEXT, deleted_at TEXT);").unwrap();
+    let otra = backup(&otra_url, &copias).unwrap();
+    backup(&database_url, &copias).unwrap();
+    assert!(otra.exists());
+    assert_eq!(list(&otra_url, &copias).unwrap().len(), 1);
+    let lista = list(&database_url, &copias).unwrap();
+    assert_eq!(lista.len(), 2);
+    assert_eq!(lista[0].registros, Some(2));
+    assert!(!primera.exists());
+    assert_eq!(stamp(&name(&database_url, "20261019-101500123", 2), &database_url), Some((String::from("20261019-101500123"), 2)));
+    assert_eq!(stamp("prueba-test-20261019-101500123.sqlite", &database_url), None);
+
+    conn.execute("DELETE FROM tipo_viviendas", []).unwrap();
+    restore(&database_url, &lista[1].path, &copias).unwrap();
+    assert_eq!(count(Path::new(&database_url)).unwrap(), 2);
+    fs::remove_dir_all(&directorio).unwrap();
+}
@@ -0,0 +1,370 @@
+use std::fmt;
+
+use crate::entidad::TipoVivienda;
+
+// Provincias por los dos primeros dígitos del código postal (01 a 52)
+const PROVINCIAS: [&str; 52] = [
+    "Araba/Álava", "Albacete", "Alicante", "Almería", "Ávila", "Badajoz", "Illes Balears", "Barcelona",
+    "Burgos", "Cáceres", "Cádiz", "Castellón", "Ciudad Real", "Córdoba", "A Coruña", "Cuenca",
+    "Girona", "Granada", "Guadalajara", "Gipuzkoa", "Huelva", "Huesca", "Jaén", "León",
+    "Lleida", "La Rioja", "Lugo", "Madrid", "Málaga", "Murcia", "Navarra", "Ourense",
+    "Asturias", "Palencia", "Las Palmas", "Pontevedra", "Salamanca", "Santa Cruz de Tenerife", "Cantabria", "Segovia",
+    "Sevilla", "Soria", "Tarragona", "Teruel", "Toledo", "Valencia", "Valladolid", "Bizkaia",
+    "Zamora", "Zaragoza", "Ceuta", "Melilla",
+];
+
+// Palabras que van en minúscula dentro del nombre de la vía
+const PARTICULAS: [&str; 9] = ["de", "del", "la", "las", "los", "el", "y", "e", "a"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TipoVia {
+    #[default]
+    Calle,
+    Avenida,
+    Plaza,
+    Paseo,
+    Camino,
+    Carretera,
+    Ronda,
+    Travesia,
+    Glorieta,
+}
+
+impl TipoVia {
+    pub const TODOS: [TipoVia; 9] = [
+        TipoVia::Calle, TipoVia::Avenida, TipoVia::Plaza, TipoVia::Paseo, TipoVia::Camino,
+        TipoVia::Carretera, TipoVia::Ronda, TipoVia::Travesia, TipoVia::Glorieta
+    ];
+
+    pub fn abreviatura(&self) -> &'static str {
+        match self {
+            TipoVia::Calle => "C/",
+            TipoVia::Avenida => "Avda.",
+            TipoVia::Plaza => "Pza.",
+            TipoVia::Paseo => "Pº",
+            TipoVia::Camino => "Cmno.",
+            TipoVia::Carretera => "Ctra.",
+            TipoVia::Ronda => "Rda.",
+            TipoVia::Travesia => "Trva.",
+            TipoVia::Glorieta => "Gta.",
+        }
+    }
+
+    // Formas reconocidas ya plegadas (minúsculas, sin tildes ni puntos)
+    fn alias(&self) -> &'static [&'static str] {
+        match self {
+            TipoVia::Calle => &["c/", "c", "cl", "calle", "cll"],
+            TipoVia::Avenida => &["av", "avd", "avda", "avenida"],
+            TipoVia::Plaza => &["pl", "pza", "plz", "plaza"],
+            TipoVia::Paseo => &["po", "pso", "paseo"],
+            TipoVia::Camino => &["cm", "cmno", "camino"],
+            TipoVia::Carretera => &["ctra", "cr", "carretera"],
+            TipoVia::Ronda => &["rda", "ronda"],
+            TipoVia::Travesia => &["trva", "tr", "travesia"],
+            TipoVia::Glorieta => &["gta", "glorieta"],
+        }
+    }
+
+    fn parse(palabra: &str) -> Option<TipoVia> {
+        let plegada = fold(palabra).replace('.', "");
+        TipoVia::TODOS.into_iter().find(|tipo_via| tipo_via.alias().contains(&plegada.as_str()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Direccion {
+    pub tipo_via: TipoVia,
+    pub nombre_via: String,
+    pub numero: Option<i32>,
+    pub piso: String,
+    pub puerta: String,
+    pub codigo_postal: String,
+    pub municipio: String,
+    pub provincia: String,
+}
+
+// Minúsculas, sin tildes y con los espacios colapsados; sirve para comparar textos
+pub fn fold(texto: &str) -> String {
+    texto.to_lowercase().chars()
+        .map(|c| match c {
+            'á' | 'à' | 'ä' | 'â' => 'a',
+            'é' | 'è' | 'ë' | 'ê' => 'e',
+            'í' | 'ì' | 'ï' | 'î' => 'i',
+            'ó' | 'ò' | 'ö' | 'ô' => 'o',
+            'ú' | 'ù' | 'ü' | 'û' => 'u',
+            'ñ' => 'n',
+            'ç' => 'c',
+            otro => otro,
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn provincia(codigo_postal: &str) -> Option<&'static str> {
+    let codigo_postal = codigo_postal.trim();
+    if codigo_postal.len() != 5 || !codigo_postal.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let prefijo: usize = codigo_postal[..2].parse().ok()?;
+    PROVINCIAS.get(prefijo.checked_sub(1)?).copied()
+}
+
+// Solo rellena la provincia si falta; no corrige la que haya escrito el usuario
+pub fn complete_provincia(p: &mut TipoVivienda) {
+    if p.provincia.trim().is_empty() {
+        p.provincia = provincia(&p.codigo_postal).unwrap_or_default().to_string();
+    }
+}
+
+fn capitalize(palabra: &str) -> String {
+    let mut letras = palabra.chars();
+    match letras.next() {
+        Some(primera) => primera.to_uppercase().chain(letras.flat_map(|c| c.to_lowercase())).collect(),
+        None => String::new(),
+    }
+}
+
+// En los nombres de vía las partículas van en minúscula también al principio: "C/ de la Paz"
+fn title_case(texto: &str, primera_mayuscula: bool) -> String {
+    texto.split_whitespace().enumerate()
+        .map(|(i, palabra)| {
+            let minuscula = palabra.to_lowercase();
+            let inicio = i == 0 && primera_mayuscula;
+            if !inicio && PARTICULAS.contains(&minuscula.as_str()) { minuscula } else { capitalize(palabra) }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn is_numero(palabra: &str) -> bool {
+    !palabra.is_empty() && palabra.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_codigo_postal(palabra: &str) -> bool {
+    palabra.len() == 5 && is_numero(palabra)
+}
+
+// "1ºC", "1º C", "3B", "bajo", "2º izda" -> (piso, puerta)
+fn parse_piso(texto: &str) -> Option<(String, String)> {
+    let texto = texto.trim();
+    let plegado = fold(texto);
+    for especial in ["bajo", "entresuelo", "atico", "principal", "sotano"] {
+        if let Some(resto) = plegado.strip_prefix(especial) {
+            return Some((capitalize(especial), resto.trim().to_uppercase()));
+        }
+    }
+    let digitos: String = texto.chars().take_while(|c| c.is_ascii_digit()).collect();
+    if digitos.is_empty() {
+        return None;
+    }
+    let resto: String = texto[digitos.len()..].trim_start_matches(|c: char| c == 'º' || c == 'ª' || c == '°' || c == '.' || c.is_whitespace()).to_string();
+    if resto.chars().count() > 4 || resto.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((digitos, resto.to_uppercase()))
+}
+
+impl Direccion {
+
+    // Formatos habituales: "C/ San Isidro 4, 1ºC", "Avda. de la Constitución, 12, 3º B, 28001 Madrid"
+    pub fn parse(texto: &str) -> Result<Direccion, String> {
+        let mut direccion = Direccion::default();
+        let texto = texto.trim();
+        if texto.is_empty() {
+            return Err(String::from("Dirección vacía"));
+        }
+        // "C/San Isidro" lleva la abreviatura pegada al nombre
+        let texto = match texto.get(..2) {
+            Some(prefijo) if prefijo.eq_ignore_ascii_case("c/") => format!("C/ {}", &texto[2..]),
+            _ => texto.to_string(),
+        };
+
+        let mut partes = texto.split(',').map(str::trim).filter(|parte| !parte.is_empty());
+        let primera = partes.next().unwrap_or_default();
+        let mut palabras: Vec<&str> = primera.split_whitespace().collect();
+        if let Some(tipo_via) = palabras.first().and_then(|palabra| TipoVia::parse(palabra)) {
+            direccion.tipo_via = tipo_via;
+            palabras.remove(0);
+        }
+        // El número y el piso pueden venir en la misma parte que el nombre: "San Isidro 4 1ºC"
+        if let Some(posicion) = palabras.iter().position(|palabra| is_numero(palabra)).filter(|posicion| *posicion > 0) {
+            direccion.numero = palabras[posicion].parse().ok();
+            let resto = palabras[posicion + 1..].join(" ");
+            if !resto.is_empty() {
+                let (piso, puerta) = parse_piso(&resto).ok_or_else(|| format!("Piso no reconocido: '{}'", resto))?;
+                direccion.piso = piso;
+                direccion.puerta = puerta;
+            }
+            palabras.truncate(posicion);
+        }
+        direccion.nombre_via = palabras.join(" ");
+        if direccion.nombre_via.is_empty() {
+            return Err(format!("Falta el nombre de la vía en '{}'", texto));
+        }
+
+        for parte in partes {
+            let primera_palabra = parte.split_whitespace().next().unwrap_or_default();
+            if direccion.numero.is_none() && is_numero(parte) {
+                direccion.numero = parte.parse().ok();
+            } else if is_codigo_postal(primera_palabra) {
+                direccion.codigo_postal = primera_palabra.to_string();
+                let resto = parte[primera_palabra.len()..].trim();
+                // "28350 Ciempozuelos (Madrid)"
+                match resto.split_once('(') {
+                    Some((municipio, provincia)) => {
+                        direccion.municipio = municipio.trim().to_string();
+                        direccion.provincia = provincia.trim_end_matches(')').trim().to_string();
+                    }
+                    None => direccion.municipio = resto.to_string(),
+                }
+            } else if direccion.piso.is_empty() && direccion.codigo_postal.is_empty() && parse_piso(parte).is_some() {
+                let (piso, puerta) = parse_piso(parte).unwrap_or_default();
+                direccion.piso = piso;
+                direccion.puerta = puerta;
+            } else if direccion.municipio.is_empty() {
+                direccion.municipio = parte.to_string();
+            } else if direccion.provincia.is_empty() {
+                direccion.provincia = parte.to_string();
+            } else {
+                return Err(format!("No se reconoce '{}' en la dirección", parte));
+            }
+        }
+        Ok(direccion)
+    }
+
+    // Parte de la calle, que puede traer la dirección completa, y completa con el resto de campos
+    pub fn from_fields(calle: &str, numero: Option<i32>, piso: &str, codigo_postal: &str, municipio: &str, provincia: &str) -> Direccion {
+        // Si la calle no se puede descomponer se conserva tal cual como nombre de la vía
+        let mut direccion = Direccion::parse(calle).unwrap_or_else(|_| Direccion { nombre_via: calle.trim().to_string(), ..Direccion::default() });
+        if direccion.numero.is_none() {
+            direccion.numero = numero;
+        }
+        if direccion.piso.is_empty() && direccion.puerta.is_empty() {
+            match parse_piso(piso) {
+                Some((piso, puerta)) => {
+                    direccion.piso = piso;
+                    direccion.puerta = puerta;
+                }
+                None => direccion.piso = piso.trim().to_string(),
+            }
+        }
+        let completar = |campo: &mut String, valor: &str| if campo.is_empty() { *campo = valor.trim().to_string() };
+        completar(&mut direccion.codigo_postal, codigo_postal);
+        completar(&mut direccion.municipio, municipio);
+        completar(&mut direccion.provincia, provincia);
+        direccion
+    }
+
+    pub fn from_tipo_vivienda(p: &TipoVivienda) -> Direccion {
+        let numero = if p.numero == 0 { None } else { Some(p.numero) };
+        Direccion::from_fields(&p.calle, numero, &p.piso, &p.codigo_postal, &p.municipio, &p.provincia)
+    }
+
+    pub fn normalize(&self) -> Direccion {
+        let provincia = match provincia(&self.codigo_postal) {
+            Some(provincia) => provincia.to_string(),
+            None => title_case(&self.provincia, true),
+        };
+        Direccion {
+            tipo_via: self.tipo_via,
+            nombre_via: title_case(&self.nombre_via, false),
+            numero: self.numero,
+            piso: capitalize(self.piso.trim()),
+            puerta: self.puerta.trim().to_uppercase(),
+            codigo_postal: self.codigo_postal.trim().to_string(),
+            municipio: title_case(&self.municipio, true),
+            provincia,
+        }
+    }
+
+    pub fn calle(&self) -> String {
+        format!("{} {}", self.tipo_via.abreviatura(), self.nombre_via)
+    }
+
+    pub fn piso_puerta(&self) -> String {
+        match (self.piso.is_empty(), self.puerta.is_empty()) {
+            (true, _) => self.puerta.clone(),
+            (false, true) if is_numero(&self.piso) => format!("{}º", self.piso),
+            (false, true) => self.piso.clone(),
+            (false, false) if is_numero(&self.piso) => format!("{}º {}", self.piso, self.puerta),
+            (false, false) => format!("{} {}", self.piso, self.puerta),
+        }
+    }
+
+    // Vuelca la dirección en los campos planos de la vivienda
+    pub fn apply(&self, p: &mut TipoVivienda) {
+        p.calle = self.calle();
+        if let Some(numero) = self.numero {
+            p.numero = numero;
+        }
+        p.piso = self.piso_puerta();
+        p.codigo_postal = self.codigo_postal.clone();
+        p.municipio = self.municipio.clone();
+        p.provincia = self.provincia.clone();
+    }
+}
+
+impl fmt::Display for Direccion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.calle())?;
+        if let Some(numero) = self.numero {
+            write!(f, " {}", numero)?;
+        }
+        if !self.piso.is_empty() || !self.puerta.is_empty() {
+            write!(f, ", {}", self.piso_puerta())?;
+        }
+        let localidad: Vec<&str> = [self.codigo_postal.as_str(), self.municipio.as_str()].into_iter().filter(|parte| !parte.is_empty()).collect();
+        if !localidad.is_empty() {
+            write!(f, ", {}", localidad.join(" "))?;
+        }
+        if !self.provincia.is_empty() && fold(&self.provincia) != fold(&self.municipio) {
+            write!(f, " ({})", self.provincia)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn parse_direcciones() {
+    let direccion = Direccion::parse("C/ San Isidro 4, 1ºC").unwrap();
+    assert_eq!(direccion.tipo_via, TipoVia::Calle);
+    assert_eq!(direccion.nombre_via, "San Isidro");
+    assert_eq!(direccion.numero, Some(4));
+    assert_eq!((direccion.piso.as_str(), direccion.puerta.as_str()), ("1", "C"));
+
+    let direccion = Direccion::parse("avda. de la constitución, 12, 3º b, 28350 Ciempozuelos (Madrid)").unwrap();
+    assert_eq!(direccion.tipo_via, TipoVia::Avenida);
+    assert_eq!(direccion.numero, Some(12));
+    assert_eq!(direccion.codigo_postal, "28350");
+    assert_eq!(direccion.municipio, "Ciempozuelos");
+    assert_eq!(direccion.provincia, "Madrid");
+
+    let direccion = Direccion::parse("C/Mayor 1, bajo").unwrap();
+    assert_eq!((direccion.nombre_via.as_str(), direccion.piso.as_str()), ("Mayor", "Bajo"));
+    assert!(Direccion::parse("").is_err());
+}
+
+#[test]
+fn normalize_direccion() {
+    let direccion = Direccion::parse("CALLE DE LA PAZ 7, 2 a, 08001 barcelona").unwrap().normalize();
+    assert_eq!(direccion.to_string(), "C/ de la Paz 7, 2º A, 08001 Barcelona");
+    assert_eq!(provincia("28350"), Some("Madrid"));
+    assert_eq!(provincia("53000"), None);
+    assert_eq!(provincia("00100"), None);
+}
+
+#[test]
+fn from_tipo_vivienda_completa_campos() {
+    let mut tipo_vivienda = TipoVivienda {
+        calle: String::from("san isidro"),
+        municipio: String::from("ciempozuelos"),
+        ..TipoVivienda::example("1")
+    };
+    Direccion::from_tipo_vivienda(&tipo_vivienda).normalize().apply(&mut tipo_vivienda);
+    assert_eq!(tipo_vivienda.calle, "C/ San Isidro");
+    assert_eq!(tipo_vivienda.piso, "1º C");
+    assert_eq!(tipo_vivienda.municipio, "Ciempozuelos");
+    assert_eq!(tipo_vivienda.provincia, "Madrid");
+}
@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+
+use crate::direccion::{fold, Direccion};
+use crate::entidad::TipoVivienda;
+
+// Dirección normalizada: "C/" y "Calle", "1C" y "1ºC", mayúsculas y tildes dan la misma clave
+pub fn key(p: &TipoVivienda) -> String {
+    let direccion = Direccion::from_tipo_vivienda(p).normalize();
+    format!("{:?}|{}|{}|{}|{}|{}",
+        direccion.tipo_via,
+        fold(&direccion.nombre_via),
+        direccion.numero.unwrap_or(0),
+        fold(&direccion.piso),
+        fold(&direccion.puerta),
+        direccion.codigo_postal)
+}
+
+// Grupos de posiciones en datos con la misma dirección, en el orden en que aparecen
+pub fn groups(datos: &[TipoVivienda]) -> Vec<Vec<usize>> {
+    let mut por_clave: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (i, p) in datos.iter().enumerate() {
+        por_clave.entry(key(p)).or_default().push(i);
+    }
+    let mut grupos: Vec<Vec<usize>> = por_clave.into_values().filter(|grupo| grupo.len() > 1).collect();
+    grupos.sort();
+    grupos
+}
+
+// Otras viviendas (distinto Id) con la misma dirección que p
+pub fn find_matches<'a>(p: &TipoVivienda, datos: &'a [TipoVivienda]) -> Vec<&'a TipoVivienda> {
+    let clave = key(p);
+    datos.iter()
+        .filter(|otra| otra.identificacion != p.identificacion && key(otra) == clave)
+        .collect()
+}
+
+// Conserva los datos de conservada y completa los que falten con los de las otras
+pub fn merge(conservada: &TipoVivienda, otras: &[TipoVivienda]) -> TipoVivienda {
+    let mut fusionada = conservada.clone();
+    for otra in otras {
+        let texto = |campo: &mut String, valor: &String| if campo.trim().is_empty() { *campo = valor.clone() };
+        texto(&mut fusionada.piso, &otra.piso);
+        texto(&mut fusionada.municipio, &otra.municipio);
+        texto(&mut fusionada.provincia, &otra.provincia);
+        texto(&mut fusionada.descripcion, &otra.descripcion);
+        let entero = |campo: &mut i32, valor: i32| if *campo == 0 { *campo = valor };
+        entero(&mut fusionada.metros_cuadrados, otra.metros_cuadrados);
+        entero(&mut fusionada.numero_aseos, otra.numero_aseos);
+        entero(&mut fusionada.numero_habitaciones, otra.numero_habitaciones);
+        if fusionada.precio == 0.0 {
+            fusionada.precio = otra.precio;
+            fusionada.moneda = otra.moneda.clone();
+        }
+        // La fecha de alta más antigua
+        if !otra.fecha_alta.is_empty() && (fusionada.fecha_alta.is_empty() || otra.fecha_alta < fusionada.fecha_alta) {
+            fusionada.fecha_alta = otra.fecha_alta.clone();
+        }
+    }
+    fusionada
+}
+
+#[cfg(test)]
+fn vivienda(id: &str, calle: &str, piso: &str) -> TipoVivienda {
+    TipoVivienda {
+        calle: calle.to_string(),
+        piso: piso.to_string(),
+        metros_cuadrados: 0,
+        ..TipoVivienda::example(id)
+    }
+}
+
+#[test]
+fn groups_direcciones_equivalentes() {
+    let datos = vec![
+        vivienda("1", "San Isidro", "1C"),
+        vivienda("2", "Chile", "1C"),
+        vivienda("3", "C/ SAN ISIDRO", "1º C"),
+        vivienda("4", "Calle San Isidró", "1ºc"),
+        vivienda("5", "San Isidro", "2C"),
+    ];
+    assert_eq!(groups(&datos), vec![vec![0, 2, 3]]);
+    assert_eq!(find_matches(&datos[0], &datos).len(), 2);
+    assert!(find_matches(&datos[4], &datos).is_empty());
+}
+
+#[test]
+fn merge_completa_campos_vacios() {
+    let conservada = vivienda("1", "San Isidro", "1C");
+    let mut otra = vivienda("2", "San Isidro", "1C");
+    otra.metros_cuadrados = 80;
+    otra.municipio = String::from("Ciempozuelos");
+    otra.numero_habitaciones = 3;
+    otra.fecha_alta = String::from("2023-01-10");
+    let fusionada = merge(&conservada, &[otra]);
+    assert_eq!(fusionada.identificacion, "1");
+    assert_eq!(fusionada.metros_cuadrados, 80);
+    assert_eq!(fusionada.numero_habitaciones, 2);
+    assert_eq!(fusionada.municipio, "Ciempozuelos");
+    assert_eq!(fusionada.fecha_alta, "2023-01-10");
+}
@@ -1,17 +1,36 @@
-use std::{path::Path, fs::{File, self}, collections::HashMap, hash::Hash, iter, result, fmt};
+use std::time::SystemTime;
+use std::{path::{Path, PathBuf}, fs::{File, self}, collections::{HashMap, HashSet}, hash::Hash, iter, result, fmt, io::{BufRead, BufReader, BufWriter, Write}};
 use serde::{Deserialize, Serialize, ser::SerializeStruct};
 use std::str::FromStr;
 use std::error::Error;
+use chrono::{Local, NaiveDate};
 use csv::Reader;
 use csv::Writer;
-use diesel::{Queryable, Insertable, Selectable, Identifiable};
-use crate::schema::tipo_viviendas;
+use diesel::{Queryable, Insertable, Selectable, Identifiable, OptionalExtension};
+use crate::schema::{auditoria, tipo_viviendas};
 use crate::repository::{TipoViviendaRepository};
+use crate::exportacion;
+use crate::estadisticas::Estadisticas;
+use crate::duplicados;
+use crate::busqueda;
+use crate::copias::{self, Copia};
+use crate::configuracion::CopiasSeguridad;
+use crate::versiones::{self, Version};
+const PATH_CSV: &str = "./src/csv/tipo-vivienda.csv";
+// Versión del sobre JSON exportado; las importaciones aceptan esta y las anteriores
+pub const VERSION_JSON: u32 = 2;
+pub const MONEDA_POR_DEFECTO: &str = "EUR";
+// avance(hechos, total) tras cada paso de una operación larga; si devuelve false la operación se cancela
+pub type Avance<'a> = &'a mut dyn FnMut(usize, usize) -> bool;
+pub const CANCELADA: &str = "Operación cancelada";
+// Por debajo de estos resultados del índice de texto completo se buscan también viviendas con erratas
+const MINIMO_BUSQUEDA: usize = 10;
+
 pub trait ScreenOutput {
     fn toScreen(&self) -> String;
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum Tipo {
     Apartamento,
@@ -19,7 +38,22 @@ pub enum Tipo {
     Chalet
 }
 
-#[derive(Debug, Deserialize,Serialize,Clone)]
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Estado {
+    #[default]
+    Disponible,
+    Reservado,
+    Vendido,
+    Alquilado
+}
+
+fn moneda_por_defecto() -> String {
+    String::from(MONEDA_POR_DEFECTO)
+}
+
+// Los campos con default permiten leer los CSV y JSON anteriores a precio y estado
+#[derive(Debug, Deserialize,Serialize,Clone,PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct TipoVivienda {
     pub identificacion: String,
@@ -30,10 +64,26 @@ pub struct TipoVivienda {
     pub metros_cuadrados: i32,
     pub numero_aseos: i32,
     pub numero_habitaciones: i32,
-    pub tipo: Tipo
+    pub tipo: Tipo,
+    #[serde(default)]
+    pub precio: f64,
+    #[serde(default = "moneda_por_defecto")]
+    pub moneda: String,
+    #[serde(default)]
+    pub estado: Estado,
+    #[serde(default)]
+    pub fecha_alta: String,
+    #[serde(default)]
+    pub descripcion: String,
+    #[serde(default)]
+    pub municipio: String,
+    #[serde(default)]
+    pub provincia: String
 }
-#[derive(Debug, Queryable, Selectable, Insertable)]
+// Default en serde para leer instantáneas de auditoria guardadas antes de añadir columnas
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Queryable, Selectable, Insertable)]
 #[diesel(table_name = tipo_viviendas)]
+#[serde(default)]
 pub struct TipoViviendaBD {
     pub identificacion: String,
     pub calle: String,
@@ -43,7 +93,50 @@ pub struct TipoViviendaBD {
     pub metros_cuadrados: i32,
     pub numero_aseos: i32,
     pub numero_habitaciones: i32,
-    pub tipo: String
+    pub tipo: String,
+    pub deleted_at: Option<String>,
+    pub precio: f64,
+    pub moneda: String,
+    pub estado: String,
+    pub fecha_alta: String,
+    pub descripcion: String,
+    pub municipio: String,
+    pub provincia: String,
+    // Se incrementa con cada modificación; una actualización solo se aplica si no ha cambiado desde que se leyó
+    pub version: i32,
+    pub updated_at: String
+}
+
+// Una operación sobre tipo_viviendas con la fila antes y después en JSON
+#[derive(Debug, Clone, Serialize, Queryable)]
+#[diesel(table_name = auditoria)]
+pub struct Auditoria {
+    pub id: i32,
+    pub operacion: String,
+    pub identificacion: String,
+    pub antes: Option<String>,
+    pub despues: Option<String>,
+    pub fecha: String,
+    pub usuario: String
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = auditoria)]
+pub struct NuevaAuditoria {
+    pub operacion: String,
+    pub identificacion: String,
+    pub antes: Option<String>,
+    pub despues: Option<String>,
+    pub fecha: String,
+    pub usuario: String
+}
+
+impl Auditoria {
+    // Campos que cambian entre antes y después como (campo, valor anterior, valor nuevo)
+    pub fn changes(&self) -> Vec<(String, String, String)> {
+        let json = |texto: &Option<String>| texto.as_deref().and_then(|texto| serde_json::from_str(texto).ok());
+        versiones::diff(json(&self.antes), json(&self.despues))
+    }
 }
 
 impl FromStr for Tipo {
@@ -70,6 +163,56 @@ impl fmt::Display for Tipo {
     }
 }
 
+impl FromStr for Estado {
+
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Estado, Self::Err> {
+        match input {
+            "Disponible" => Ok(Estado::Disponible),
+            "Reservado" => Ok(Estado::Reservado),
+            "Vendido" => Ok(Estado::Vendido),
+            "Alquilado" => Ok(Estado::Alquilado),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Estado {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Estado::Disponible => write!(f, "Disponible"),
+            Estado::Reservado => write!(f, "Reservado"),
+            Estado::Vendido => write!(f, "Vendido"),
+            Estado::Alquilado => write!(f, "Alquilado")
+        }
+    }
+}
+
+// Vivienda de ejemplo para las pruebas, que cambian lo que necesiten con ..TipoVivienda::example(id)
+#[cfg(test)]
+impl TipoVivienda {
+    pub fn example(identificacion: &str) -> TipoVivienda {
+        TipoVivienda {
+            identificacion: identificacion.to_string(),
+            calle: String::from("San Isidro"),
+            numero: 4,
+            piso: String::from("1C"),
+            codigo_postal: String::from("28350"),
+            metros_cuadrados: 80,
+            numero_aseos: 1,
+            numero_habitaciones: 2,
+            tipo: Tipo::Apartamento,
+            precio: 0.0,
+            moneda: String::from(MONEDA_POR_DEFECTO),
+            estado: Estado::Disponible,
+            fecha_alta: String::new(),
+            descripcion: String::new(),
+            municipio: String::new(),
+            provincia: String::new()
+        }
+    }
+}
 
 impl ScreenOutput for TipoVivienda {
     fn toScreen(&self) -> String {
@@ -80,9 +223,143 @@ impl ScreenOutput for TipoVivienda {
 
 
 
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ResumenGuardado {
+    pub altas: usize,
+    pub bajas: usize,
+    pub modificaciones: usize,
+}
+
+impl fmt::Display for ResumenGuardado {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let plural = |n: usize, singular: &str, plural: &str| format!("{} {}", n, if n == 1 { singular } else { plural });
+        let mut partes = Vec::new();
+        if self.altas > 0 {
+            partes.push(plural(self.altas, "alta", "altas"));
+        }
+        if self.bajas > 0 {
+            partes.push(plural(self.bajas, "baja", "bajas"));
+        }
+        if self.modificaciones > 0 {
+            partes.push(plural(self.modificaciones, "modificación", "modificaciones"));
+        }
+        if partes.is_empty() {
+            write!(f, "Guardado: sin cambios")
+        } else {
+            write!(f, "Guardado: {}", partes.join(", "))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Formato {
+    Csv,
+    Json,
+    JsonLines,
+    Xlsx,
+    Ods
+}
+
+impl Formato {
+    pub fn from_path(path: &Path) -> Formato {
+        match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+            Some("json") => Formato::Json,
+            Some("jsonl") | Some("ndjson") => Formato::JsonLines,
+            Some("xlsx") => Formato::Xlsx,
+            Some("ods") => Formato::Ods,
+            _ => Formato::Csv,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportacionJson {
+    pub version: u32,
+    pub tipo_viviendas: Vec<TipoVivienda>,
+}
+
+// Las primeras exportaciones eran un array sin sobre
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ImportacionJson {
+    Sobre(ExportacionJson),
+    Lista(Vec<TipoVivienda>),
+}
+
+pub fn parse_json(texto: &str) -> Result<Vec<TipoVivienda>, Box<dyn Error>> {
+    match serde_json::from_str::<ImportacionJson>(texto)? {
+        ImportacionJson::Sobre(sobre) if sobre.version > VERSION_JSON => {
+            Err(format!("Versión de exportación no soportada: {} (máxima {})", sobre.version, VERSION_JSON).into())
+        }
+        ImportacionJson::Sobre(sobre) => Ok(sobre.tipo_viviendas),
+        ImportacionJson::Lista(datos) => Ok(datos),
+    }
+}
+
+// Admite "1250.5", "250.000", "1.250,50" y "1250 €"; un punto seguido de tres cifras separa miles
+pub fn parse_precio(valor: &str) -> Result<f64, String> {
+    let limpio: String = valor.chars().filter(|c| !c.is_whitespace() && *c != '€').collect();
+    let miles = |texto: &str| texto.split('.').skip(1).all(|grupo| grupo.len() == 3 && grupo.chars().all(|c| c.is_ascii_digit()));
+    let normalizado = if limpio.contains(',') {
+        limpio.replace('.', "").replace(',', ".")
+    } else if limpio.contains('.') && miles(&limpio) {
+        limpio.replace('.', "")
+    } else {
+        limpio
+    };
+    match normalizado.parse::<f64>() {
+        Ok(precio) if precio.is_finite() && precio >= 0.0 => Ok(precio),
+        _ => Err(format!("Precio no válido: '{}'", valor)),
+    }
+}
+
+// Fechas de alta en formato AAAA-MM-DD; vacía si no se conoce
+pub fn parse_fecha(valor: &str) -> Result<String, String> {
+    let valor = valor.trim();
+    if valor.is_empty() {
+        return Ok(String::new());
+    }
+    NaiveDate::parse_from_str(valor, "%Y-%m-%d")
+        .map(|fecha| fecha.format("%Y-%m-%d").to_string())
+        .map_err(|_| format!("Fecha no válida (AAAA-MM-DD): '{}'", valor))
+}
+
+pub fn today() -> String {
+    Local::now().format("%Y-%m-%d").to_string()
+}
+
 pub struct TipoViviendaDAO {
     indice : HashMap<String,TipoVivienda>,
     repository: TipoViviendaRepository,
+    path_csv: Option<PathBuf>,
+    // Sin configurar no se hacen copias al guardar
+    copias: Option<CopiasSeguridad>,
+    // Estado del CSV y de la base de datos tras la última lectura o escritura propia
+    huella_csv: Option<(SystemTime, u64)>,
+    data_version: i64,
+    // Versión de cada fila cuando se leyó o se guardó desde aquí, para detectar conflictos al modificarla
+    versiones_leidas: HashMap<String, i32>,
+}
+
+// Viviendas y versiones tal como están en disco, leídas sin tocar las que tiene el DAO
+pub struct Instantanea {
+    indice : HashMap<String,TipoVivienda>,
+    versiones_leidas: HashMap<String, i32>,
+    huella_csv: Option<(SystemTime, u64)>,
+    data_version: i64,
+}
+
+impl Instantanea {
+    pub fn datos(&self) -> Vec<TipoVivienda> {
+        self.indice.values().cloned().collect()
+    }
+}
+
+// Fecha de modificación y tamaño: basta para saber si otro programa ha reescrito el fichero
+fn fingerprint(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
 }
 
 
@@ -95,67 +372,403 @@ impl ScreenOutput for TipoViviendaDAO {
 
 impl TipoViviendaDAO {
     pub fn new() -> TipoViviendaDAO {
-        let mut p = TipoViviendaDAO { indice : HashMap::new(), repository: TipoViviendaRepository::new()  };
-        p.refresh();
-        p
+        TipoViviendaDAO::try_new().unwrap()
+    }
+
+    // Base de datos de DATABASE_URL y datos.csv; falla en vez de abortar si no hay configuración
+    pub fn try_new() -> Result<TipoViviendaDAO, Box<dyn Error>> {
+        let mut p = TipoViviendaDAO { indice : HashMap::new(), repository: TipoViviendaRepository::try_new().map_err(|e| e as Box<dyn Error>)?, path_csv: Some(PathBuf::from(PATH_CSV)), copias: None, huella_csv: None, data_version: 0, versiones_leidas: HashMap::new() };
+        p.load()?;
+        Ok(p)
+    }
+
+    // Sin CSV: los datos se leen y escriben solo en la base de datos indicada
+    pub fn open(database_url: &str) -> Result<TipoViviendaDAO, Box<dyn Error>> {
+        let mut p = TipoViviendaDAO { indice : HashMap::new(), repository: TipoViviendaRepository::open(database_url).map_err(|e| e as Box<dyn Error>)?, path_csv: None, copias: None, huella_csv: None, data_version: 0, versiones_leidas: HashMap::new() };
+        p.load()?;
+        Ok(p)
+    }
+
+    // Copia del DAO sobre otra conexión del pool, para trabajar desde otro hilo sin bloquear este
+    pub fn pooled(&self) -> Result<TipoViviendaDAO, Box<dyn Error>> {
+        Ok(TipoViviendaDAO {
+            indice: self.indice.clone(),
+            repository: self.repository.pooled().map_err(|e| e as Box<dyn Error>)?,
+            path_csv: self.path_csv.clone(),
+            copias: self.copias.clone(),
+            huella_csv: self.huella_csv,
+            data_version: self.data_version,
+            versiones_leidas: self.versiones_leidas.clone(),
+        })
+    }
+
+    // Se queda con lo que ha leído y guardado una copia hecha con pooled. data_version es de cada
+    // conexión, así que se vuelve a leer en la propia para no tomar por ajeno lo guardado por la copia
+    pub fn adopt(&mut self, copia: TipoViviendaDAO) -> Result<(), Box<dyn Error>> {
+        self.indice = copia.indice;
+        self.versiones_leidas = copia.versiones_leidas;
+        self.mark_read()
     }
 
     pub fn refresh(&mut self) {
-        let path_csv =  Path::new("./src/csv/tipo-vivienda.csv");
-        self.indice.clear();
-        let mut rdr = Reader::from_path(path_csv).unwrap();
+        self.load().unwrap();
+    }
 
-        //let mut iter = rdr.deserialize();
-        for result in rdr.deserialize() {
-            let record: TipoVivienda = result.unwrap();
-            self.indice.insert(record.clone().identificacion,record);
-        }  
+    fn load(&mut self) -> Result<(), Box<dyn Error>> {
+        let instantanea = self.snapshot()?;
+        self.accept(instantanea);
+        Ok(())
+    }
+
+    pub fn snapshot(&mut self) -> Result<Instantanea, Box<dyn Error>> {
+        let mut indice = HashMap::new();
+        let versiones_leidas = self.repository.versions()?.into_iter().collect();
+        match self.path_csv.clone() {
+            Some(path_csv) => {
+                let mut rdr = Reader::from_path(path_csv)?;
+                for result in rdr.deserialize() {
+                    let record: TipoVivienda = result?;
+                    indice.insert(record.clone().identificacion,record);
+                }
+            }
+            None => {
+                for tipoViviendaBD in self.repository.find_all()? {
+                    let record: TipoVivienda = self.convert_bd(tipoViviendaBD)?;
+                    indice.insert(record.clone().identificacion,record);
+                }
+            }
+        }
+        Ok(Instantanea {
+            indice,
+            versiones_leidas,
+            huella_csv: self.path_csv.as_deref().and_then(fingerprint),
+            data_version: self.repository.data_version()?,
+        })
+    }
+
+    // Pasa a trabajar con lo leído en la instantánea
+    pub fn accept(&mut self, instantanea: Instantanea) {
+        self.indice = instantanea.indice;
+        self.versiones_leidas = instantanea.versiones_leidas;
+        self.huella_csv = instantanea.huella_csv;
+        self.data_version = instantanea.data_version;
+    }
+
+    // Da por vistos los cambios sin cargarlos: no se vuelve a avisar de ellos y, al guardar,
+    // las viviendas que hayan cambiado en disco darán conflicto de versión
+    pub fn ignore(&mut self, instantanea: &Instantanea) {
+        self.huella_csv = instantanea.huella_csv;
+        self.data_version = instantanea.data_version;
+    }
+
+    fn mark_read(&mut self) -> Result<(), Box<dyn Error>> {
+        self.huella_csv = self.path_csv.as_deref().and_then(fingerprint);
+        self.data_version = self.repository.data_version()?;
+        Ok(())
+    }
+
+    // Cambios hechos por otros programas desde la última lectura o escritura propia.
+    // data_version de SQLite solo cambia con transacciones de otras conexiones.
+    pub fn changed_on_disk(&mut self) -> Result<bool, Box<dyn Error>> {
+        let huella_csv = self.path_csv.as_deref().and_then(fingerprint);
+        Ok(huella_csv != self.huella_csv || self.repository.data_version()? != self.data_version)
+    }
+
+    pub fn reload(&mut self) -> Result<(), Box<dyn Error>> {
+        self.load()
+    }
+
+    // Tras escribir desde aquí sin pasar por save las versiones leídas pasan a ser las actuales
+    fn refresh_versions(&mut self, keys : &[String]) -> Result<(), Box<dyn Error>> {
+        for (id, version) in self.repository.versions()? {
+            if keys.contains(&id) {
+                self.versiones_leidas.insert(id, version);
+            }
+        }
+        Ok(())
+    }
+
+    // Resolución de un ConflictoVersion quedándose con lo que hay en la base de datos; None si ya no existe
+    pub fn accept_remote(&mut self, key : &str) -> Result<Option<TipoVivienda>, Box<dyn Error>> {
+        let actual = match self.repository.find_by_id(key.to_string()).optional()? {
+            Some(fila) => {
+                self.versiones_leidas.insert(key.to_string(), fila.version);
+                if fila.deleted_at.is_none() { Some(self.convert_bd(fila)?) } else { None }
+            }
+            None => {
+                self.versiones_leidas.remove(key);
+                None
+            }
+        };
+        match &actual {
+            Some(tipo_vivienda) => self.indice.insert(key.to_string(), tipo_vivienda.clone()),
+            None => self.indice.remove(key),
+        };
+        Ok(actual)
+    }
+
+    // Resolución de un ConflictoVersion sobrescribiendo la fila con p sea cual sea su versión
+    pub fn overwrite(&mut self, p : &TipoVivienda) -> Result<TipoVivienda, Box<dyn Error>> {
+        self.accept_remote(&p.identificacion)?;
+        let tipoViviendaBD = self.convert(p.clone());
+        let guardada = match self.versiones_leidas.contains_key(&p.identificacion) {
+            true => self.repository.update(p.identificacion.clone(), tipoViviendaBD).map_err(|e| e as Box<dyn Error>)?,
+            false => self.repository.create(&tipoViviendaBD)?,
+        };
+        self.versiones_leidas.insert(guardada.identificacion.clone(), guardada.version);
+        self.indice.insert(p.identificacion.clone(), p.clone());
+        self.sync_csv()?;
+        Ok(p.clone())
     }
     
 
-    pub fn save (&mut self, datos : &Vec<TipoVivienda>) -> Result<(), Box<dyn Error>> {
-        let path_json =  Path::new("./src/csv/tipo-vivienda.csv");
-        let mut wtr = Writer::from_path(path_json)?;
-        //let mut wtr = Writer::from_writer(vec![]);
-        self.repository.deleteAll();
+    // Las viviendas que ya no están en datos se mueven a la papelera en lugar de borrarse
+    pub fn save (&mut self, datos : &Vec<TipoVivienda>) -> Result<ResumenGuardado, Box<dyn Error>> {
+        self.save_with(datos, &mut |_, _| true)
+    }
+
+    // Un guardado no se puede cancelar a medias: avance solo informa
+    pub fn save_with(&mut self, datos : &Vec<TipoVivienda>, avance : Avance) -> Result<ResumenGuardado, Box<dyn Error>> {
+        let resumen = self.summary(datos);
+        if self.copias.is_some() {
+            self.backup()?;
+        }
+        // Con CSV manda el fichero; sin él solo se tocan las viviendas leídas o cambiadas aquí,
+        // para no borrar ni pisar lo que otros hayan guardado mientras tanto
+        let con_csv = self.path_csv.is_some();
+        let mut borradas = Vec::new();
+        for tipoViviendaBD in self.repository.find_all()? {
+            let id = tipoViviendaBD.identificacion;
+            if !datos.iter().any(|tipo_vivienda| tipo_vivienda.identificacion == id) && (con_csv || self.indice.contains_key(&id)) {
+                borradas.push(id);
+            }
+        }
+        let (mut altas, mut modificaciones) = (Vec::new(), Vec::new());
         for tipo_vivienda in datos {
+            // convert toma la versión leída también con CSV: si la fila ha cambiado desde entonces hay conflicto
             let tipoViviendaBD: TipoViviendaBD = self.convert(tipo_vivienda.clone());
-            self.repository.create(&tipoViviendaBD);
-            wtr.serialize(tipo_vivienda)?
-           
+            // Solo se da de alta si no existe; cualquier otro error (bloqueo, E/S) se devuelve tal cual
+            match self.repository.find_by_id(tipoViviendaBD.identificacion.clone()).optional()? {
+                Some(fila) => {
+                    let sin_cambios = fila.deleted_at.is_none() && self.convert_bd(fila)? == *tipo_vivienda;
+                    if !sin_cambios && (con_csv || self.indice.get(&tipo_vivienda.identificacion) != Some(tipo_vivienda)) {
+                        modificaciones.push(tipoViviendaBD);
+                    }
+                }
+                None => altas.push(tipoViviendaBD),
+            }
+        }
+        // Todo o nada: con un conflicto no quedan cambios a medias en la base de datos ni se toca el CSV
+        let guardadas = self.repository.save_all(&borradas, &altas, &modificaciones, &mut *avance).map_err(|e| e as Box<dyn Error>)?;
+        for id in &borradas {
+            self.indice.remove(id);
+        }
+        for fila in guardadas {
+            self.versiones_leidas.insert(fila.identificacion, fila.version);
+        }
+        for tipo_vivienda in datos {
+            self.indice.insert(tipo_vivienda.identificacion.clone(), tipo_vivienda.clone());
+        }
+        if let Some(path_csv) = self.path_csv.clone() {
+            self.export_csv(&path_csv, datos, &mut |_, _| true)?;
+        }
+        self.mark_read()?;
+        avance(datos.len(), datos.len());
+        Ok(resumen)
+    }
+
+    pub fn export_csv(&self, path: &Path, datos : &[TipoVivienda], avance : Avance) -> Result<(), Box<dyn Error>> {
+        let mut wtr = Writer::from_path(path)?;
+        for (i, tipo_vivienda) in datos.iter().enumerate() {
+            wtr.serialize(tipo_vivienda)?;
+            if !avance(i + 1, datos.len()) {
+                return Err(CANCELADA.into());
+            }
         }
         wtr.flush()?;
         Ok(())
     }
 
+    pub fn export_json(&self, path: &Path, datos : &[TipoVivienda]) -> Result<(), Box<dyn Error>> {
+        let sobre = ExportacionJson { version: VERSION_JSON, tipo_viviendas: datos.to_vec() };
+        let mut wtr = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut wtr, &sobre)?;
+        wtr.flush()?;
+        Ok(())
+    }
 
-    pub fn save_and_refresh(&mut self, datos: &Vec<TipoVivienda>) {
-        self.save(datos);
+    pub fn export_jsonl(&self, path: &Path, datos : &[TipoVivienda], avance : Avance) -> Result<(), Box<dyn Error>> {
+        let mut wtr = BufWriter::new(File::create(path)?);
+        for (i, tipo_vivienda) in datos.iter().enumerate() {
+            serde_json::to_writer(&mut wtr, tipo_vivienda)?;
+            writeln!(wtr)?;
+            if !avance(i + 1, datos.len()) {
+                return Err(CANCELADA.into());
+            }
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    pub fn export(&self, path: &Path, datos : &[TipoVivienda]) -> Result<(), Box<dyn Error>> {
+        self.export_with(path, datos, &mut |_, _| true)
+    }
+
+    // JSON, Excel y ODS se escriben de una vez: solo informan y se cancelan al terminar.
+    // Si se cancela no se deja el fichero a medio escribir
+    pub fn export_with(&self, path: &Path, datos : &[TipoVivienda], avance : Avance) -> Result<(), Box<dyn Error>> {
+        let resultado = match Formato::from_path(path) {
+            Formato::Csv => self.export_csv(path, datos, &mut *avance),
+            Formato::Json => self.export_json(path, datos),
+            Formato::JsonLines => self.export_jsonl(path, datos, &mut *avance),
+            Formato::Xlsx => exportacion::export_xlsx(path, datos),
+            Formato::Ods => exportacion::export_ods(path, datos),
+        };
+        let resultado = match resultado {
+            Ok(()) if !avance(datos.len(), datos.len()) => Err(CANCELADA.into()),
+            otro => otro,
+        };
+        if resultado.is_err() && path.exists() {
+            fs::remove_file(path)?;
+        }
+        resultado
+    }
+
+    // En las importaciones el avance se mide en bytes leídos del fichero
+    pub fn import_csv(&self, path: &Path, avance : Avance) -> Result<Vec<TipoVivienda>, Box<dyn Error>> {
+        let total = fs::metadata(path)?.len() as usize;
+        let mut rdr = Reader::from_path(path)?;
+        let mut datos = Vec::new();
+        let mut records = rdr.deserialize();
+        while let Some(result) = records.next() {
+            datos.push(result?);
+            if !avance(records.reader().position().byte() as usize, total) {
+                return Err(CANCELADA.into());
+            }
+        }
+        Ok(datos)
+    }
+
+    pub fn import_json(&self, path: &Path) -> Result<Vec<TipoVivienda>, Box<dyn Error>> {
+        parse_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn import_jsonl(&self, path: &Path, avance : Avance) -> Result<Vec<TipoVivienda>, Box<dyn Error>> {
+        let total = fs::metadata(path)?.len() as usize;
+        let mut leidos = 0;
+        let mut datos = Vec::new();
+        for (i, linea) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let linea = linea?;
+            leidos += linea.len() + 1;
+            if !avance(leidos.min(total), total) {
+                return Err(CANCELADA.into());
+            }
+            if linea.trim().is_empty() {
+                continue;
+            }
+            datos.push(serde_json::from_str(&linea).map_err(|e| format!("Línea {}: {}", i + 1, e))?);
+        }
+        Ok(datos)
+    }
+
+    pub fn import(&self, path: &Path) -> Result<Vec<TipoVivienda>, Box<dyn Error>> {
+        self.import_with(path, &mut |_, _| true)
+    }
+
+    pub fn import_with(&self, path: &Path, avance : Avance) -> Result<Vec<TipoVivienda>, Box<dyn Error>> {
+        let datos = match Formato::from_path(path) {
+            Formato::Csv => self.import_csv(path, &mut *avance)?,
+            Formato::Json => self.import_json(path)?,
+            Formato::JsonLines => self.import_jsonl(path, &mut *avance)?,
+            Formato::Xlsx | Formato::Ods => return Err(format!("No se puede importar desde {}", path.display()).into()),
+        };
+        let total = fs::metadata(path)?.len() as usize;
+        if !avance(total, total) {
+            return Err(CANCELADA.into());
+        }
+        Ok(datos)
+    }
+
+    // Tras una operación masiva el CSV se reescribe con lo ya persistido en indice
+    fn sync_csv(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(path_csv) = &self.path_csv {
+            self.export_csv(path_csv, &self.asVector(), &mut |_, _| true)?;
+        }
+        self.mark_read()
+    }
+
+
+    pub fn save_and_refresh(&mut self, datos: &Vec<TipoVivienda>) -> Result<ResumenGuardado, Box<dyn Error>> {
+        let resumen = self.save(datos)?;
         self.refresh();
+        Ok(resumen)
+    }
+
+    pub fn summary(&self, datos: &Vec<TipoVivienda>) -> ResumenGuardado {
+        let mut resumen = ResumenGuardado::default();
+        for tipo_vivienda in datos {
+            match self.indice.get(&tipo_vivienda.identificacion) {
+                None => resumen.altas += 1,
+                Some(anterior) if anterior != tipo_vivienda => resumen.modificaciones += 1,
+                _ => {}
+            }
+        }
+        resumen.bajas = self.indice.keys()
+            .filter(|id| !datos.iter().any(|tipo_vivienda| &tipo_vivienda.identificacion == *id))
+            .count();
+        resumen
     }
 
 
+    // El índice de texto completo ordena por bm25 pero no admite erratas: si encuentra pocas viviendas,
+    // detrás van las que solo encajan aproximadamente, de la más a la menos parecida
+    pub fn search(&mut self, texto: &str) -> Result<Vec<TipoVivienda>, Box<dyn Error>> {
+        let mut encontradas = self.repository.search(texto)?.into_iter()
+            .map(|fila| self.convert_bd(fila))
+            .collect::<Result<Vec<TipoVivienda>, Box<dyn Error>>>()?;
+        if encontradas.len() >= MINIMO_BUSQUEDA {
+            return Ok(encontradas);
+        }
+        let ids: HashSet<String> = encontradas.iter().map(|e| e.identificacion.clone()).collect();
+        let mut parecidas: Vec<(f64, &TipoVivienda)> = self.indice.values()
+            .filter(|e| !ids.contains(&e.identificacion))
+            .filter_map(|e| busqueda::score(e, texto).map(|puntuacion| (puntuacion, e)))
+            .collect();
+        // A igual parecido por Id, para que el orden no dependa del HashMap
+        parecidas.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.identificacion.cmp(&b.1.identificacion)));
+        encontradas.extend(parecidas.into_iter().map(|(_, e)| e.clone()));
+        Ok(encontradas)
+    }
+
     pub fn asVector(&self) -> Vec<TipoVivienda> {
         let datos = self.indice.values().cloned().collect::<Vec<TipoVivienda>>();
         datos
     }
 
-    pub fn add(&mut self, p : TipoVivienda) {
+    // Devuelve los Id de las viviendas ya existentes con la misma dirección
+    pub fn add(&mut self, p : TipoVivienda) -> Result<Vec<String>, Box<dyn Error>> {
+        let existentes = self.asVector();
+        let duplicadas: Vec<String> = duplicados::find_matches(&p, &existentes).iter().map(|e| e.identificacion.clone()).collect();
         if !self.indice.contains_key(&p.identificacion) {
             let tipoViviendaBD: TipoViviendaBD = self.convert(p.clone());
+            let fila = self.repository.create(&tipoViviendaBD)?;
+            self.versiones_leidas.insert(fila.identificacion, fila.version);
             self.indice.insert(p.clone().identificacion, p);
-            self.repository.create(&tipoViviendaBD);
-
         }
+        Ok(duplicadas)
     } 
 
-    pub fn update(&mut self, p : TipoVivienda) {
-        if self.indice.contains_key(&p.identificacion) {
-            let tipoViviendaBD: TipoViviendaBD = self.convert(p.clone());
-            let id:String = p.clone().identificacion;
-            self.indice.insert(p.clone().identificacion, p);
-            self.repository.update(id,tipoViviendaBD);
+    // Un ConflictoVersion llega tal cual, para resolverlo igual que al guardar
+    pub fn update(&mut self, p : TipoVivienda) -> Result<TipoVivienda, Box<dyn Error>> {
+        if !self.indice.contains_key(&p.identificacion) {
+            return Err(format!("No existe la vivienda {}", p.identificacion).into());
         }
+        let tipoViviendaBD: TipoViviendaBD = self.convert(p.clone());
+        let fila = self.repository.update(p.identificacion.clone(), tipoViviendaBD).map_err(|e| e as Box<dyn Error>)?;
+        self.versiones_leidas.insert(fila.identificacion, fila.version);
+        self.indice.insert(p.identificacion.clone(), p.clone());
+        self.sync_csv()?;
+        Ok(p)
     } 
 
     pub fn remove(&mut self, key : &String) -> Option<TipoVivienda> {
@@ -163,11 +776,137 @@ impl TipoViviendaDAO {
         self.indice.remove(key)
     }
 
+    pub fn remove_many(&mut self, keys : &[String]) -> Result<usize, Box<dyn Error>> {
+        let borrados = self.repository.delete_many(keys)?;
+        self.refresh_versions(keys)?;
+        for key in keys {
+            self.indice.remove(key);
+        }
+        self.sync_csv()?;
+        Ok(borrados)
+    }
+
+    pub fn update_tipo_many(&mut self, keys : &[String], tipo : Tipo) -> Result<usize, Box<dyn Error>> {
+        let modificados = self.repository.update_tipo_many(keys, &format!("{:?}", tipo))?;
+        self.refresh_versions(keys)?;
+        for key in keys {
+            if let Some(tipo_vivienda) = self.indice.get_mut(key) {
+                tipo_vivienda.tipo = tipo.clone();
+            }
+        }
+        self.sync_csv()?;
+        Ok(modificados)
+    }
+
+    pub fn update_codigo_postal_many(&mut self, keys : &[String], codigo_postal : &str) -> Result<usize, Box<dyn Error>> {
+        let modificados = self.repository.update_codigo_postal_many(keys, codigo_postal)?;
+        self.refresh_versions(keys)?;
+        for key in keys {
+            if let Some(tipo_vivienda) = self.indice.get_mut(key) {
+                tipo_vivienda.codigo_postal = codigo_postal.to_string();
+            }
+        }
+        self.sync_csv()?;
+        Ok(modificados)
+    }
+
+    // Guarda la fusionada y manda las descartadas a la papelera en una sola transacción
+    pub fn merge(&mut self, fusionada : &TipoVivienda, descartadas : &[String]) -> Result<usize, Box<dyn Error>> {
+        let tipoViviendaBD = self.convert(fusionada.clone());
+        let borradas = self.repository.merge(&tipoViviendaBD, descartadas)?;
+        self.refresh_versions(descartadas)?;
+        self.refresh_versions(&[fusionada.identificacion.clone()])?;
+        for key in descartadas {
+            self.indice.remove(key);
+        }
+        self.indice.insert(fusionada.identificacion.clone(), fusionada.clone());
+        self.sync_csv()?;
+        Ok(borradas)
+    }
+
+    // Con base de datos las estadísticas se agregan en SQL; con CSV, en memoria
+    pub fn statistics(&mut self) -> Result<Estadisticas, Box<dyn Error>> {
+        match self.path_csv {
+            Some(_) => Ok(Estadisticas::calculate(&self.asVector())),
+            None => Ok(Estadisticas::from_repository(&mut self.repository)?),
+        }
+    }
+
     pub fn deleteAll(&mut self) {
         self.repository.deleteAll();
     }
 
+    // Viviendas en la papelera junto con la fecha en que se borraron
+    pub fn deleted(&mut self) -> Result<Vec<(TipoVivienda, String)>, Box<dyn Error>> {
+        let mut borrados = Vec::new();
+        for tipoViviendaBD in self.repository.find_deleted()? {
+            let fecha = tipoViviendaBD.deleted_at.clone().unwrap_or_default();
+            borrados.push((self.convert_bd(tipoViviendaBD)?, fecha));
+        }
+        Ok(borrados)
+    }
+
+    pub fn restore(&mut self, key : &String) -> Result<TipoVivienda, Box<dyn Error>> {
+        let tipoViviendaBD = self.repository.restore(key)?;
+        self.versiones_leidas.insert(key.clone(), tipoViviendaBD.version);
+        let tipo_vivienda = self.convert_bd(tipoViviendaBD)?;
+        self.indice.insert(tipo_vivienda.clone().identificacion, tipo_vivienda.clone());
+        self.sync_csv()?;
+        Ok(tipo_vivienda)
+    }
+
+    pub fn purge(&mut self, key : &String) -> Result<(), Box<dyn Error>> {
+        self.repository.purge(key)?;
+        Ok(())
+    }
+
+    pub fn versions(&mut self, key : &str) -> Result<Vec<Version>, Box<dyn Error>> {
+        Ok(versiones::timeline(&self.history(key)?))
+    }
+
+    // La versión pasa a ser la actual, aunque la vivienda estuviera en la papelera o purgada
+    pub fn restore_version(&mut self, version : &Version) -> Result<TipoVivienda, Box<dyn Error>> {
+        let mut tipoViviendaBD = version.datos.clone().ok_or_else(|| format!("La versión {} no tiene datos", version.numero))?;
+        tipoViviendaBD.deleted_at = None;
+        let restaurada = self.repository.restore_version(&tipoViviendaBD)?;
+        self.versiones_leidas.insert(restaurada.identificacion.clone(), restaurada.version);
+        let tipo_vivienda = self.convert_bd(restaurada)?;
+        self.indice.insert(tipo_vivienda.identificacion.clone(), tipo_vivienda.clone());
+        self.sync_csv()?;
+        Ok(tipo_vivienda)
+    }
+
+    pub fn enable_backups(&mut self, copias : CopiasSeguridad) {
+        self.copias = Some(copias);
+    }
+
+    pub fn backup(&self) -> Result<PathBuf, Box<dyn Error>> {
+        copias::backup(&self.repository.database_url, &self.copias.clone().unwrap_or_default())
+    }
+
+    pub fn backups(&self) -> Result<Vec<Copia>, Box<dyn Error>> {
+        copias::list(&self.repository.database_url, &self.copias.clone().unwrap_or_default())
+    }
+
+    // Sustituye la base de datos por la copia y vuelve a leer las viviendas de ella
+    pub fn restore_backup(&mut self, copia : &Path) -> Result<(), Box<dyn Error>> {
+        copias::restore(&self.repository.database_url, copia, &self.copias.clone().unwrap_or_default())?;
+        self.indice.clear();
+        self.versiones_leidas = self.repository.versions()?.into_iter().collect();
+        for tipoViviendaBD in self.repository.find_all()? {
+            let record: TipoVivienda = self.convert_bd(tipoViviendaBD)?;
+            self.indice.insert(record.clone().identificacion,record);
+        }
+        self.sync_csv()
+    }
+
+    // Operaciones registradas sobre una vivienda, de la más reciente a la más antigua
+    pub fn history(&mut self, key : &str) -> Result<Vec<Auditoria>, Box<dyn Error>> {
+        Ok(self.repository.find_history(key)?)
+    }
+
     pub fn convert(&mut self, p:TipoVivienda) -> TipoViviendaBD {
+        let version = self.versiones_leidas.get(&p.identificacion).copied().unwrap_or(1);
         return super::entidad::TipoViviendaBD {
             identificacion: p.identificacion,
             calle: p.calle,
@@ -177,11 +916,44 @@ impl TipoViviendaDAO {
             metros_cuadrados: p.metros_cuadrados,
             numero_aseos: p.numero_aseos,
             numero_habitaciones: p.numero_habitaciones,
-            tipo:  format!("{:?}", p.tipo)
+            tipo:  format!("{:?}", p.tipo),
+            deleted_at: None,
+            precio: p.precio,
+            moneda: p.moneda,
+            estado: p.estado.to_string(),
+            fecha_alta: p.fecha_alta,
+            descripcion: p.descripcion,
+            municipio: p.municipio,
+            provincia: p.provincia,
+            version,
+            updated_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
             }
             
    
     }      
+
+    pub fn convert_bd(&self, p:TipoViviendaBD) -> Result<TipoVivienda, Box<dyn Error>> {
+        let tipo = Tipo::from_str(&p.tipo).map_err(|_| format!("Tipo desconocido: {}", p.tipo))?;
+        let estado = Estado::from_str(&p.estado).map_err(|_| format!("Estado desconocido: {}", p.estado))?;
+        Ok(TipoVivienda {
+            identificacion: p.identificacion,
+            calle: p.calle,
+            numero: p.numero,
+            piso: p.piso,
+            codigo_postal: p.codigo_postal,
+            metros_cuadrados: p.metros_cuadrados,
+            numero_aseos: p.numero_aseos,
+            numero_habitaciones: p.numero_habitaciones,
+            tipo,
+            precio: p.precio,
+            moneda: p.moneda,
+            estado,
+            fecha_alta: p.fecha_alta,
+            descripcion: p.descripcion,
+            municipio: p.municipio,
+            provincia: p.provincia
+        })
+    }
     
 }
 
@@ -196,10 +968,59 @@ fn to_screen_tipo_vivienda() {
     metros_cuadrados: 80,
     numero_aseos: 1,
     numero_habitaciones: 2,
-    tipo: super::Tipo::Apartamento};
+    tipo: super::Tipo::Apartamento,
+    precio: 0.0,
+    moneda: String::from("EUR"),
+    estado: Estado::Disponible,
+    fecha_alta: String::new(),
+    descripcion: String::new(),
+    municipio: String::new(),
+    provincia: String::new()};
     assert_eq!(tipo_vivienda.toScreen(),"\"1\",\"San Isidro\",4,\"1C\",\"28350\",80,1,2,Apartamento");
 }
 
+#[test]
+fn resumen_guardado_to_string() {
+    let resumen = ResumenGuardado { altas: 3, bajas: 1, modificaciones: 0 };
+    assert_eq!(resumen.to_string(), "Guardado: 3 altas, 1 baja");
+    assert_eq!(ResumenGuardado::default().to_string(), "Guardado: sin cambios");
+}
+
+#[test]
+fn parse_json_sobre_y_lista() {
+    let sobre = r#"{"version":1,"tipoViviendas":[{"identificacion":"1","calle":"San Isidro","numero":4,"piso":"1C","codigoPostal":"28350","metrosCuadrados":80,"numeroAseos":1,"numeroHabitaciones":2,"tipo":"apartamento"}]}"#;
+    let datos = parse_json(sobre).unwrap();
+    assert_eq!(datos[0].toScreen(), "\"1\",\"San Isidro\",4,\"1C\",\"28350\",80,1,2,Apartamento");
+
+    let lista = r#"[{"identificacion":"2","calle":"Chile","numero":40,"piso":"","codigoPostal":"28350","metrosCuadrados":100,"numeroAseos":3,"numeroHabitaciones":3,"tipo":"chalet"}]"#;
+    assert_eq!(parse_json(lista).unwrap()[0].identificacion, "2");
+
+    assert!(parse_json(r#"{"version":99,"tipoViviendas":[]}"#).is_err());
+}
+
+#[test]
+fn parse_precio_y_fecha() {
+    assert_eq!(parse_precio("1.250,50 €"), Ok(1250.5));
+    assert_eq!(parse_precio("98000"), Ok(98000.0));
+    assert!(parse_precio("-1").is_err());
+    assert_eq!(parse_precio("250.000"), Ok(250000.0));
+    assert_eq!(parse_precio("1.250.000 €"), Ok(1250000.0));
+    assert_eq!(parse_precio("1250.5"), Ok(1250.5));
+    assert!(parse_precio("inf").is_err());
+    assert!(parse_precio("NaN").is_err());
+    assert_eq!(parse_fecha("2023-5-9"), Ok(String::from("2023-05-09")));
+    assert!(parse_fecha("09/05/2023").is_err());
+}
+
+#[test]
+fn csv_sin_campos_nuevos() {
+    let csv = "identificacion,calle,numero,piso,codigoPostal,metrosCuadrados,numeroAseos,numeroHabitaciones,tipo\n1,San Isidro,4,1C,28350,80,1,2,apartamento\n";
+    let tipo_vivienda: TipoVivienda = Reader::from_reader(csv.as_bytes()).deserialize().next().unwrap().unwrap();
+    assert_eq!(tipo_vivienda.moneda, MONEDA_POR_DEFECTO);
+    assert_eq!(tipo_vivienda.estado, Estado::Disponible);
+    assert_eq!(tipo_vivienda.precio, 0.0);
+}
+
 #[test]
 fn as_vector_tipo_vivienda() {
     let mut tipo_vivienda_dao = TipoViviendaDAO::new();
@@ -219,9 +1040,16 @@ fn add_tipo_vivienda() {
         metros_cuadrados: 100,
         numero_aseos: 3,
         numero_habitaciones: 3,
-        tipo: super::Tipo::Chalet
+        tipo: super::Tipo::Chalet,
+        precio: 0.0,
+        moneda: String::from("EUR"),
+        estado: Estado::Disponible,
+        fecha_alta: String::new(),
+        descripcion: String::new(),
+        municipio: String::new(),
+        provincia: String::new()
     };
-    tipo_vivienda_dao.add(tipo_vivienda);
+    tipo_vivienda_dao.add(tipo_vivienda).unwrap();
 
     let datos:  Vec<TipoVivienda> = tipo_vivienda_dao.asVector();
     for tipo_vivienda in datos {
@@ -252,11 +1080,18 @@ fn save_an_refresh_tipo_vivienda() {
         metros_cuadrados: 100,
         numero_aseos: 3,
         numero_habitaciones: 3,
-        tipo: super::Tipo::Chalet
+        tipo: super::Tipo::Chalet,
+        precio: 0.0,
+        moneda: String::from("EUR"),
+        estado: Estado::Disponible,
+        fecha_alta: String::new(),
+        descripcion: String::new(),
+        municipio: String::new(),
+        provincia: String::new()
     };
     let mut tipo_vivienda_dao = TipoViviendaDAO::new();  
     tipo_vivienda_dao.deleteAll(); 
-    tipo_vivienda_dao.add(tipo_vivienda);
+    tipo_vivienda_dao.add(tipo_vivienda).unwrap();
     tipo_vivienda_dao.save_and_refresh(&tipo_vivienda_dao.asVector());
 
     let datos:  Vec<TipoVivienda> = tipo_vivienda_dao.asVector();
@@ -269,3 +1104,122 @@ fn save_an_refresh_tipo_vivienda() {
     tipo_vivienda_dao.remove(&String::from("2"));
     tipo_vivienda_dao.save_and_refresh(&tipo_vivienda_dao.asVector());
 }
+
+#[test]
+fn history_registra_cada_operacion() {
+    let mut tipo_vivienda_dao = TipoViviendaDAO::open(":memory:").unwrap();
+    let mut tipo_vivienda = TipoVivienda::example("1");
+    tipo_vivienda_dao.save(&vec![tipo_vivienda.clone()]).unwrap();
+    // Guardar sin cambios no genera registros
+    tipo_vivienda_dao.save(&vec![tipo_vivienda.clone()]).unwrap();
+    tipo_vivienda.metros_cuadrados = 85;
+    tipo_vivienda_dao.save(&vec![tipo_vivienda.clone()]).unwrap();
+    tipo_vivienda_dao.remove_many(&[String::from("1")]).unwrap();
+
+    let historial = tipo_vivienda_dao.history("1").unwrap();
+    let operaciones: Vec<&str> = historial.iter().map(|registro| registro.operacion.as_str()).collect();
+    assert_eq!(operaciones, vec!["borrado", "modificacion", "alta"]);
+    assert_eq!(historial[1].changes(), vec![(String::from("metros_cuadrados"), String::from("80"), String::from("85"))]);
+    assert!(historial[2].antes.is_none());
+    assert!(!historial[0].usuario.is_empty());
+}
+
+#[test]
+fn restore_vuelve_al_csv() {
+    let path = std::env::temp_dir().join(format!("papelera-{}.csv", std::process::id()));
+    let mut tipo_vivienda_dao = TipoViviendaDAO::open(":memory:").unwrap();
+    tipo_vivienda_dao.path_csv = Some(path.clone());
+    tipo_vivienda_dao.save(&vec![TipoVivienda::example("1"), TipoVivienda::example("2")]).unwrap();
+    tipo_vivienda_dao.remove_many(&[String::from("2")]).unwrap();
+    tipo_vivienda_dao.restore(&String::from("2")).unwrap();
+    let en_csv: Vec<TipoVivienda> = Reader::from_path(&path).unwrap().deserialize().map(|fila| fila.unwrap()).collect();
+    assert_eq!(en_csv.len(), 2);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn changed_on_disk_solo_con_cambios_ajenos() {
+    let path = std::env::temp_dir().join(format!("vigilancia-{}.sqlite", std::process::id()));
+    let database_url = path.to_string_lossy().to_string();
+    let mut propio = TipoViviendaDAO::open(&database_url).unwrap();
+    let mut ajeno = TipoViviendaDAO::open(&database_url).unwrap();
+    let tipo_vivienda = TipoVivienda::example("1");
+    propio.save(&vec![tipo_vivienda]).unwrap();
+    assert!(!propio.changed_on_disk().unwrap());
+    assert!(ajeno.changed_on_disk().unwrap());
+    // Ignorar no carga nada pero deja de avisar
+    let instantanea = ajeno.snapshot().unwrap();
+    ajeno.ignore(&instantanea);
+    assert!(!ajeno.changed_on_disk().unwrap());
+    assert!(ajeno.asVector().is_empty());
+    ajeno.accept(instantanea);
+    assert_eq!(ajeno.asVector().len(), 1);
+    drop((propio, ajeno));
+    for sufijo in ["", "-wal", "-shm"] {
+        let _ = fs::remove_file(format!("{}{}", path.to_string_lossy(), sufijo));
+    }
+}
+
+#[test]
+fn save_detecta_conflicto_de_version() {
+    let path = std::env::temp_dir().join(format!("conflicto-{}.sqlite", std::process::id()));
+    let database_url = path.to_string_lossy().to_string();
+    let mut tipo_vivienda = TipoVivienda::example("1");
+    let mut ana = TipoViviendaDAO::open(&database_url).unwrap();
+    ana.save(&vec![tipo_vivienda.clone()]).unwrap();
+    let mut luis = TipoViviendaDAO::open(&database_url).unwrap();
+
+    tipo_vivienda.metros_cuadrados = 90;
+    ana.save(&vec![tipo_vivienda.clone()]).unwrap();
+    // Guardar sin haber tocado la vivienda no pisa el cambio de ana
+    luis.save(&luis.asVector()).unwrap();
+    tipo_vivienda.metros_cuadrados = 100;
+    let mut nueva = tipo_vivienda.clone();
+    nueva.identificacion = String::from("2");
+    let error = luis.save(&vec![nueva, tipo_vivienda.clone()]).unwrap_err();
+    let conflicto = error.downcast_ref::<crate::repository::ConflictoVersion>().unwrap();
+    assert_eq!(conflicto.actual.as_ref().unwrap().metros_cuadrados, 90);
+    // El conflicto deshace todo el guardado, también el alta que iba antes
+    assert!(luis.history("2").unwrap().is_empty());
+    // update tampoco pisa el cambio ni toca el índice
+    let error = luis.update(tipo_vivienda.clone()).unwrap_err();
+    assert!(error.downcast_ref::<crate::repository::ConflictoVersion>().is_some());
+    assert_eq!(luis.asVector()[0].metros_cuadrados, 80);
+
+    luis.overwrite(&tipo_vivienda).unwrap();
+    ana.reload().unwrap();
+    assert_eq!(ana.asVector()[0].metros_cuadrados, 100);
+    drop((ana, luis));
+    for sufijo in ["", "-wal", "-shm"] {
+        let _ = fs::remove_file(format!("{}{}", path.to_string_lossy(), sufijo));
+    }
+}
+
+#[test]
+fn search_por_palabras_en_cualquier_campo() {
+    let vivienda = |id: &str, calle: &str, tipo: Tipo, codigo_postal: &str, municipio: &str| TipoVivienda {
+        calle: calle.to_string(),
+        tipo,
+        codigo_postal: codigo_postal.to_string(),
+        municipio: municipio.to_string(),
+        provincia: String::from("Madrid"),
+        ..TipoVivienda::example(id)
+    };
+    let mut tipo_vivienda_dao = TipoViviendaDAO::open(":memory:").unwrap();
+    tipo_vivienda_dao.save(&vec![
+        vivienda("1", "San Isidro", Tipo::Chalet, "28350", "Ciempozuelos"),
+        vivienda("2", "San Isidro", Tipo::Apartamento, "28350", "Ciempozuelos"),
+        vivienda("3", "Isidro Fernández", Tipo::Chalet, "28931", "Móstoles"),
+    ]).unwrap();
+    let ids = |tipo_vivienda_dao: &mut TipoViviendaDAO, texto: &str| tipo_vivienda_dao.search(texto).unwrap()
+        .iter().map(|e| e.identificacion.clone()).collect::<Vec<String>>();
+
+    assert_eq!(ids(&mut tipo_vivienda_dao, "san isidro chalet 28350"), vec!["1"]);
+    assert_eq!(ids(&mut tipo_vivienda_dao, "MOSTOLES"), vec!["3"]);
+    assert_eq!(ids(&mut tipo_vivienda_dao, "isid").len(), 3);
+    assert!(tipo_vivienda_dao.search("\"san* AND (").unwrap().is_empty());
+    // Con erratas en la calle solo la encuentra la búsqueda aproximada
+    assert_eq!(ids(&mut tipo_vivienda_dao, "isdiro fernadez"), vec!["3"]);
+    tipo_vivienda_dao.remove_many(&[String::from("1")]).unwrap();
+    assert_eq!(ids(&mut tipo_vivienda_dao, "chalet"), vec!["3"]);
+}
@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+use serde::Serialize;
+
+use crate::entidad::TipoVivienda;
+use crate::repository::{Columna, FilaGrupo, TipoViviendaRepository};
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResumenMetros {
+    pub media: f64,
+    pub mediana: f64,
+    pub minimo: i32,
+    pub maximo: i32,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Grupo {
+    pub total: usize,
+    pub metros: ResumenMetros,
+    pub habitaciones_media: f64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Estadisticas {
+    pub total: usize,
+    pub metros: ResumenMetros,
+    pub habitaciones: BTreeMap<i32, usize>,
+    pub aseos: BTreeMap<i32, usize>,
+    pub por_tipo: BTreeMap<String, Grupo>,
+    pub por_codigo_postal: BTreeMap<String, Grupo>,
+}
+
+fn metros_summary(metros: &mut Vec<i32>) -> ResumenMetros {
+    if metros.is_empty() {
+        return ResumenMetros::default();
+    }
+    metros.sort();
+    let n = metros.len();
+    let mediana = if n % 2 == 1 {
+        metros[n / 2] as f64
+    } else {
+        (metros[n / 2 - 1] + metros[n / 2]) as f64 / 2.0
+    };
+    ResumenMetros {
+        media: metros.iter().map(|m| *m as f64).sum::<f64>() / n as f64,
+        mediana,
+        minimo: metros[0],
+        maximo: metros[n - 1],
+    }
+}
+
+fn group(datos: &[&TipoVivienda]) -> Grupo {
+    let mut metros: Vec<i32> = datos.iter().map(|p| p.metros_cuadrados).collect();
+    Grupo {
+        total: datos.len(),
+        metros: metros_summary(&mut metros),
+        habitaciones_media: datos.iter().map(|p| p.numero_habitaciones as f64).sum::<f64>() / datos.len().max(1) as f64,
+    }
+}
+
+// Intervalos [desde, hasta) de metros cuadrados con un ancho redondeado a decenas
+pub fn histogram(datos: &[TipoVivienda], intervalos: usize) -> Vec<(i32, i32, usize)> {
+    let (minimo, maximo) = match (datos.iter().map(|p| p.metros_cuadrados).min(), datos.iter().map(|p| p.metros_cuadrados).max()) {
+        (Some(minimo), Some(maximo)) => (minimo, maximo),
+        _ => return Vec::new(),
+    };
+    let rango = (maximo - minimo + 1) as f64 / intervalos.max(1) as f64;
+    let ancho = ((rango / 10.0).ceil() as i32).max(1) * 10;
+    let inicio = minimo.div_euclid(ancho) * ancho;
+    let mut barras: Vec<(i32, i32, usize)> = (inicio..=maximo).step_by(ancho as usize)
+        .map(|desde| (desde, desde + ancho, 0))
+        .collect();
+    for p in datos {
+        barras[((p.metros_cuadrados - inicio) / ancho) as usize].2 += 1;
+    }
+    barras
+}
+
+fn group_sql(fila: &FilaGrupo) -> Grupo {
+    Grupo {
+        total: fila.total as usize,
+        metros: ResumenMetros { media: fila.media, mediana: fila.mediana, minimo: fila.minimo, maximo: fila.maximo },
+        habitaciones_media: fila.habitaciones_media,
+    }
+}
+
+impl Estadisticas {
+
+    // Cálculo en memoria, para el modelo de la GUI, listas filtradas y el origen CSV
+    pub fn calculate(datos: &[TipoVivienda]) -> Estadisticas {
+        let todos: Vec<&TipoVivienda> = datos.iter().collect();
+        let mut por_tipo: BTreeMap<String, Vec<&TipoVivienda>> = BTreeMap::new();
+        let mut por_codigo_postal: BTreeMap<String, Vec<&TipoVivienda>> = BTreeMap::new();
+        let mut habitaciones = BTreeMap::new();
+        let mut aseos = BTreeMap::new();
+        for p in datos {
+            por_tipo.entry(p.tipo.to_string()).or_default().push(p);
+            por_codigo_postal.entry(p.codigo_postal.clone()).or_default().push(p);
+            *habitaciones.entry(p.numero_habitaciones).or_insert(0) += 1;
+            *aseos.entry(p.numero_aseos).or_insert(0) += 1;
+        }
+        let general = group(&todos);
+        Estadisticas {
+            total: general.total,
+            metros: general.metros,
+            habitaciones,
+            aseos,
+            por_tipo: por_tipo.into_iter().map(|(clave, grupo)| (clave, group(&grupo))).collect(),
+            por_codigo_postal: por_codigo_postal.into_iter().map(|(clave, grupo)| (clave, group(&grupo))).collect(),
+        }
+    }
+
+    // Mismo resultado que calculate pero agregado en SQLite con GROUP BY
+    pub fn from_repository(repository: &mut TipoViviendaRepository) -> Result<Estadisticas, diesel::result::Error> {
+        let general = repository.group_by(None)?.first().map(group_sql).unwrap_or_default();
+        let grupos = |filas: Vec<FilaGrupo>| -> BTreeMap<String, Grupo> { filas.iter().map(|fila| (fila.clave.clone(), group_sql(fila))).collect() };
+        let recuentos = |columna: Columna, repository: &mut TipoViviendaRepository| -> Result<BTreeMap<i32, usize>, diesel::result::Error> {
+            Ok(repository.count_by(columna)?.into_iter().map(|fila| (fila.valor, fila.total as usize)).collect())
+        };
+        Ok(Estadisticas {
+            total: general.total,
+            metros: general.metros,
+            habitaciones: recuentos(Columna::Habitaciones, repository)?,
+            aseos: recuentos(Columna::Aseos, repository)?,
+            por_tipo: grupos(repository.group_by(Some(Columna::Tipo))?),
+            por_codigo_postal: grupos(repository.group_by(Some(Columna::CodigoPostal))?),
+        })
+    }
+
+    // Texto para el panel de la GUI y la salida de tabla del CLI
+    pub fn lines(&self) -> Vec<String> {
+        let mut lineas = vec![
+            format!("Viviendas: {}", self.total),
+            format!("Metros cuadrados: media {:.1}, mediana {:.1}, mínimo {}, máximo {}",
+                self.metros.media, self.metros.mediana, self.metros.minimo, self.metros.maximo),
+            String::new(),
+            String::from("Habitaciones:"),
+        ];
+        for (habitaciones, total) in &self.habitaciones {
+            lineas.push(format!("  {}: {}", habitaciones, total));
+        }
+        lineas.push(String::from("Aseos:"));
+        for (aseos, total) in &self.aseos {
+            lineas.push(format!("  {}: {}", aseos, total));
+        }
+        for (titulo, grupos) in [("Por tipo:", &self.por_tipo), ("Por código postal:", &self.por_codigo_postal)] {
+            lineas.push(String::new());
+            lineas.push(String::from(titulo));
+            for (clave, grupo) in grupos {
+                lineas.push(format!("  {}: {} viviendas, {:.1} m² de media (mediana {:.1}, {}-{}), {:.1} habitaciones de media",
+                    clave, grupo.total, grupo.metros.media, grupo.metros.mediana, grupo.metros.minimo, grupo.metros.maximo, grupo.habitaciones_media));
+            }
+        }
+        lineas
+    }
+}
+
+#[cfg(test)]
+fn sample() -> Vec<TipoVivienda> {
+    use crate::entidad::Tipo;
+    let vivienda = |id: &str, cp: &str, metros: i32, habitaciones: i32, tipo: Tipo| TipoVivienda {
+        piso: String::new(),
+        codigo_postal: cp.to_string(),
+        metros_cuadrados: metros,
+        numero_habitaciones: habitaciones,
+        tipo,
+        ..TipoVivienda::example(id)
+    };
+    vec![
+        vivienda("1", "28350", 80, 2, Tipo::Apartamento),
+        vivienda("2", "28350", 60, 1, Tipo::Apartamento),
+        vivienda("3", "28001", 150, 4, Tipo::Chalet),
+        vivienda("4", "28001", 100, 3, Tipo::Apartamento),
+    ]
+}
+
+#[test]
+fn calculate_estadisticas() {
+    let estadisticas = Estadisticas::calculate(&sample());
+    assert_eq!(estadisticas.total, 4);
+    assert_eq!(estadisticas.metros, ResumenMetros { media: 97.5, mediana: 90.0, minimo: 60, maximo: 150 });
+    assert_eq!(estadisticas.habitaciones.get(&2), Some(&1));
+    assert_eq!(estadisticas.por_tipo["Apartamento"].total, 3);
+    assert_eq!(estadisticas.por_tipo["Apartamento"].metros.mediana, 80.0);
+    assert_eq!(estadisticas.por_codigo_postal["28001"].habitaciones_media, 3.5);
+}
+
+#[test]
+fn statistics_sql_igual_que_en_memoria() {
+    let mut tipo_vivienda_dao = crate::entidad::TipoViviendaDAO::open(":memory:").unwrap();
+    tipo_vivienda_dao.save_and_refresh(&sample()).unwrap();
+    assert_eq!(tipo_vivienda_dao.statistics().unwrap(), Estadisticas::calculate(&sample()));
+}
+
+#[test]
+fn histogram_metros() {
+    assert_eq!(histogram(&sample(), 5), vec![(60, 80, 1), (80, 100, 1), (100, 120, 1), (120, 140, 0), (140, 160, 1)]);
+    assert!(histogram(&[], 5).is_empty());
+}
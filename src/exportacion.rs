@@ -0,0 +1,192 @@
+use std::{error::Error, fs::File, io::Write, path::Path};
+use rust_xlsxwriter::{Format, Workbook};
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::entidad::TipoVivienda;
+use crate::estadisticas::Estadisticas;
+
+// Cabecera y ancho (en caracteres) de cada columna de la hoja de viviendas
+const COLUMNAS: [(&str, f64); 16] = [
+    ("Id", 10.0),
+    ("Calle", 30.0),
+    ("Número", 9.0),
+    ("Piso", 8.0),
+    ("Código Postal", 14.0),
+    ("Municipio", 20.0),
+    ("Provincia", 16.0),
+    ("Metros", 9.0),
+    ("Aseos", 8.0),
+    ("Habitaciones", 13.0),
+    ("Tipo", 14.0),
+    ("Precio", 12.0),
+    ("Moneda", 8.0),
+    ("Estado", 12.0),
+    ("Fecha de alta", 13.0),
+    ("Descripción", 40.0),
+];
+
+const COLUMNAS_RESUMEN: [(&str, f64); 7] = [
+    ("Tipo", 14.0),
+    ("Viviendas", 11.0),
+    ("Metros medios", 15.0),
+    ("Metros (mediana)", 17.0),
+    ("Metros mínimos", 15.0),
+    ("Metros máximos", 15.0),
+    ("Habitaciones medias", 20.0),
+];
+
+enum Celda {
+    Texto(String),
+    Numero(f64),
+}
+
+fn cells(p: &TipoVivienda) -> Vec<Celda> {
+    vec![
+        Celda::Texto(p.identificacion.clone()),
+        Celda::Texto(p.calle.clone()),
+        Celda::Numero(p.numero as f64),
+        Celda::Texto(p.piso.clone()),
+        // Texto para no perder los ceros a la izquierda
+        Celda::Texto(p.codigo_postal.clone()),
+        Celda::Texto(p.municipio.clone()),
+        Celda::Texto(p.provincia.clone()),
+        Celda::Numero(p.metros_cuadrados as f64),
+        Celda::Numero(p.numero_aseos as f64),
+        Celda::Numero(p.numero_habitaciones as f64),
+        Celda::Texto(p.tipo.to_string()),
+        Celda::Numero(p.precio),
+        Celda::Texto(p.moneda.clone()),
+        Celda::Texto(p.estado.to_string()),
+        Celda::Texto(p.fecha_alta.clone()),
+        Celda::Texto(p.descripcion.clone()),
+    ]
+}
+
+fn summary_cells(datos: &[TipoVivienda]) -> Vec<Vec<Celda>> {
+    Estadisticas::calculate(datos).por_tipo.into_iter().map(|(tipo, grupo)| vec![
+        Celda::Texto(tipo),
+        Celda::Numero(grupo.total as f64),
+        Celda::Numero(grupo.metros.media),
+        Celda::Numero(grupo.metros.mediana),
+        Celda::Numero(grupo.metros.minimo as f64),
+        Celda::Numero(grupo.metros.maximo as f64),
+        Celda::Numero(grupo.habitaciones_media),
+    ]).collect()
+}
+
+pub fn export_xlsx(path: &Path, datos: &[TipoVivienda]) -> Result<(), Box<dyn Error>> {
+    let mut workbook = Workbook::new();
+    let negrita = Format::new().set_bold();
+    let hojas = [
+        ("Viviendas", &COLUMNAS[..], datos.iter().map(cells).collect::<Vec<_>>()),
+        ("Resumen", &COLUMNAS_RESUMEN[..], summary_cells(datos)),
+    ];
+    for (nombre, columnas, filas) in hojas {
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(nombre)?;
+        for (c, (cabecera, ancho)) in columnas.iter().enumerate() {
+            worksheet.write_string_with_format(0, c as u16, *cabecera, &negrita)?;
+            worksheet.set_column_width(c as u16, *ancho)?;
+        }
+        for (r, fila) in filas.iter().enumerate() {
+            for (c, celda) in fila.iter().enumerate() {
+                match celda {
+                    Celda::Texto(texto) => worksheet.write_string(r as u32 + 1, c as u16, texto)?,
+                    Celda::Numero(numero) => worksheet.write_number(r as u32 + 1, c as u16, *numero)?,
+                };
+            }
+        }
+        worksheet.set_freeze_panes(1, 0)?;
+    }
+    workbook.save(path)?;
+    Ok(())
+}
+
+fn escape_xml(texto: &str) -> String {
+    texto.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn ods_table(nombre: &str, columnas: &[(&str, f64)], filas: &[Vec<Celda>], primer_estilo: usize) -> String {
+    let mut xml = format!("<table:table table:name=\"{}\">", escape_xml(nombre));
+    for c in 0..columnas.len() {
+        xml.push_str(&format!("<table:table-column table:style-name=\"co{}\"/>", primer_estilo + c));
+    }
+    xml.push_str("<table:table-row>");
+    for (cabecera, _) in columnas {
+        xml.push_str(&format!("<table:table-cell table:style-name=\"cabecera\" office:value-type=\"string\"><text:p>{}</text:p></table:table-cell>", escape_xml(cabecera)));
+    }
+    xml.push_str("</table:table-row>");
+    for fila in filas {
+        xml.push_str("<table:table-row>");
+        for celda in fila {
+            match celda {
+                Celda::Texto(texto) => xml.push_str(&format!("<table:table-cell office:value-type=\"string\"><text:p>{}</text:p></table:table-cell>", escape_xml(texto))),
+                Celda::Numero(numero) => xml.push_str(&format!("<table:table-cell office:value-type=\"float\" office:value=\"{}\"><text:p>{}</text:p></table:table-cell>", numero, numero)),
+            }
+        }
+        xml.push_str("</table:table-row>");
+    }
+    xml.push_str("</table:table>");
+    xml
+}
+
+fn ods_content(datos: &[TipoVivienda]) -> String {
+    let mut estilos = String::new();
+    for (i, (_, ancho)) in COLUMNAS.iter().chain(COLUMNAS_RESUMEN.iter()).enumerate() {
+        // Aproximadamente 0,2 cm por carácter
+        estilos.push_str(&format!(
+            "<style:style style:name=\"co{}\" style:family=\"table-column\"><style:table-column-properties style:column-width=\"{:.2}cm\"/></style:style>",
+            i, ancho * 0.2));
+    }
+    estilos.push_str("<style:style style:name=\"cabecera\" style:family=\"table-cell\"><style:text-properties fo:font-weight=\"bold\"/></style:style>");
+
+    let viviendas: Vec<Vec<Celda>> = datos.iter().map(cells).collect();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+<office:document-content xmlns:office=\"urn:oasis:names:tc:opendocument:xmlns:office:1.0\" \
+xmlns:style=\"urn:oasis:names:tc:opendocument:xmlns:style:1.0\" \
+xmlns:text=\"urn:oasis:names:tc:opendocument:xmlns:text:1.0\" \
+xmlns:table=\"urn:oasis:names:tc:opendocument:xmlns:table:1.0\" \
+xmlns:fo=\"urn:oasis:names:tc:opendocument:xmlns:xsl-fo-compatible:1.0\" office:version=\"1.2\">\
+<office:automatic-styles>{}</office:automatic-styles>\
+<office:body><office:spreadsheet>{}{}</office:spreadsheet></office:body>\
+</office:document-content>",
+        estilos,
+        ods_table("Viviendas", &COLUMNAS, &viviendas, 0),
+        ods_table("Resumen", &COLUMNAS_RESUMEN, &summary_cells(datos), COLUMNAS.len()))
+}
+
+const ODS_MIMETYPE: &str = "application/vnd.oasis.opendocument.spreadsheet";
+
+const ODS_MANIFEST: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+<manifest:manifest xmlns:manifest=\"urn:oasis:names:tc:opendocument:xmlns:manifest:1.0\" manifest:version=\"1.2\">\
+<manifest:file-entry manifest:full-path=\"/\" manifest:version=\"1.2\" manifest:media-type=\"application/vnd.oasis.opendocument.spreadsheet\"/>\
+<manifest:file-entry manifest:full-path=\"content.xml\" manifest:media-type=\"text/xml\"/>\
+</manifest:manifest>";
+
+pub fn export_ods(path: &Path, datos: &[TipoVivienda]) -> Result<(), Box<dyn Error>> {
+    let mut zip = ZipWriter::new(File::create(path)?);
+    // El mimetype debe ir el primero y sin comprimir
+    zip.start_file("mimetype", FileOptions::default().compression_method(CompressionMethod::Stored))?;
+    zip.write_all(ODS_MIMETYPE.as_bytes())?;
+    zip.start_file("META-INF/manifest.xml", FileOptions::default())?;
+    zip.write_all(ODS_MANIFEST.as_bytes())?;
+    zip.start_file("content.xml", FileOptions::default())?;
+    zip.write_all(ods_content(datos).as_bytes())?;
+    zip.finish()?;
+    Ok(())
+}
+
+#[test]
+fn ods_content_tipos_de_celda() {
+    let datos = vec![TipoVivienda {
+        calle: String::from("San Isidro & Co"),
+        codigo_postal: String::from("08001"),
+        ..TipoVivienda::example("1")
+    }];
+    let xml = ods_content(&datos);
+    assert!(xml.contains("<text:p>San Isidro &amp; Co</text:p>"));
+    assert!(xml.contains("office:value-type=\"string\"><text:p>08001</text:p>"));
+    assert!(xml.contains("office:value-type=\"float\" office:value=\"80\""));
+    assert!(xml.contains("<table:table table:name=\"Resumen\">"));
+}
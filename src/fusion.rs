@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use crate::entidad::TipoVivienda;
+
+// Una vivienda cambiada a la vez aquí y en disco; None si en ese lado se ha borrado
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflicto {
+    pub identificacion: String,
+    pub local: Option<TipoVivienda>,
+    pub remoto: Option<TipoVivienda>,
+}
+
+#[derive(Debug, Default)]
+pub struct Fusion {
+    // Los conflictos quedan resueltos con la versión local hasta que se elija otra
+    pub resultado: Vec<TipoVivienda>,
+    pub conflictos: Vec<Conflicto>,
+}
+
+fn index(datos: &[TipoVivienda]) -> HashMap<&str, &TipoVivienda> {
+    datos.iter().map(|e| (e.identificacion.as_str(), e)).collect()
+}
+
+// Fusión a tres bandas: base es lo último leído de disco, local los cambios sin guardar y remoto lo que hay ahora en disco
+pub fn merge(base: &[TipoVivienda], local: &[TipoVivienda], remoto: &[TipoVivienda]) -> Fusion {
+    let (indice_base, indice_local, indice_remoto) = (index(base), index(local), index(remoto));
+    // Primero el orden de local y después las viviendas nuevas en disco
+    let mut ids: Vec<&str> = local.iter().map(|e| e.identificacion.as_str()).collect();
+    ids.extend(remoto.iter().map(|e| e.identificacion.as_str()).filter(|id| !indice_local.contains_key(id)));
+    ids.extend(base.iter().map(|e| e.identificacion.as_str()).filter(|id| !indice_local.contains_key(id) && !indice_remoto.contains_key(id)));
+
+    let mut fusion = Fusion::default();
+    for id in ids {
+        let (b, l, r) = (indice_base.get(id), indice_local.get(id), indice_remoto.get(id));
+        let elegida = if l == r || r == b {
+            l
+        } else if l == b {
+            r
+        } else {
+            fusion.conflictos.push(Conflicto {
+                identificacion: id.to_string(),
+                local: l.map(|e| (*e).clone()),
+                remoto: r.map(|e| (*e).clone()),
+            });
+            l
+        };
+        if let Some(tipo_vivienda) = elegida {
+            fusion.resultado.push((*tipo_vivienda).clone());
+        }
+    }
+    fusion
+}
+
+// Sustituye en resultado la versión local de un conflicto por la remota
+pub fn take_remote(resultado: &mut Vec<TipoVivienda>, conflicto: &Conflicto) {
+    let posicion = resultado.iter().position(|e| e.identificacion == conflicto.identificacion);
+    match (posicion, &conflicto.remoto) {
+        (Some(i), Some(remoto)) => resultado[i] = remoto.clone(),
+        (Some(i), None) => {
+            resultado.remove(i);
+        }
+        (None, Some(remoto)) => resultado.push(remoto.clone()),
+        (None, None) => {}
+    }
+}
+
+// Fusión campo a campo de una vivienda: los cambios de cada lado se combinan y, si los dos cambian
+// el mismo campo, gana el local. Devuelve también los campos en conflicto.
+pub fn merge_fields(base: &TipoVivienda, local: &TipoVivienda, remoto: &TipoVivienda) -> (TipoVivienda, Vec<String>) {
+    let campos = |tipo_vivienda: &TipoVivienda| match serde_json::to_value(tipo_vivienda) {
+        Ok(serde_json::Value::Object(campos)) => campos,
+        _ => serde_json::Map::new(),
+    };
+    let (campos_base, campos_local, campos_remoto) = (campos(base), campos(local), campos(remoto));
+    let mut fusionados = campos_local.clone();
+    let mut conflictos = Vec::new();
+    for (campo, valor_remoto) in &campos_remoto {
+        let (valor_base, valor_local) = (campos_base.get(campo), campos_local.get(campo));
+        if valor_local == valor_base {
+            fusionados.insert(campo.clone(), valor_remoto.clone());
+        } else if Some(valor_remoto) != valor_base && Some(valor_remoto) != valor_local {
+            conflictos.push(campo.clone());
+        }
+    }
+    let fusionada = serde_json::from_value(serde_json::Value::Object(fusionados)).unwrap_or_else(|_| local.clone());
+    (fusionada, conflictos)
+}
+
+#[test]
+fn merge_tres_bandas() {
+    let vivienda = |id: &str, metros: i32| TipoVivienda { metros_cuadrados: metros, ..TipoVivienda::example(id) };
+    let base = vec![vivienda("1", 80), vivienda("2", 80), vivienda("3", 80), vivienda("4", 80)];
+    // 1 cambia solo aquí, 2 solo en disco, 3 en los dos lados, 4 se borra en disco, 5 es nueva aquí y 6 en disco
+    let local = vec![vivienda("1", 90), vivienda("2", 80), vivienda("3", 95), vivienda("4", 80), vivienda("5", 70)];
+    let remoto = vec![vivienda("1", 80), vivienda("2", 85), vivienda("3", 100), vivienda("6", 60)];
+    let mut fusion = merge(&base, &local, &remoto);
+    let metros: Vec<(&str, i32)> = fusion.resultado.iter().map(|e| (e.identificacion.as_str(), e.metros_cuadrados)).collect();
+    assert_eq!(metros, vec![("1", 90), ("2", 85), ("3", 95), ("5", 70), ("6", 60)]);
+    assert_eq!(fusion.conflictos.len(), 1);
+    assert_eq!(fusion.conflictos[0].identificacion, "3");
+
+    let conflicto = fusion.conflictos[0].clone();
+    take_remote(&mut fusion.resultado, &conflicto);
+    assert_eq!(fusion.resultado[2].metros_cuadrados, 100);
+
+    let mut local = vivienda("1", 90);
+    local.piso = String::from("2A");
+    let mut remoto = vivienda("1", 100);
+    remoto.numero_aseos = 2;
+    let (fusionada, conflictos) = merge_fields(&base[0], &local, &remoto);
+    assert_eq!((fusionada.metros_cuadrados, fusionada.piso.as_str(), fusionada.numero_aseos), (90, "2A", 2));
+    assert_eq!(conflictos, vec![String::from("metrosCuadrados")]);
+}
@@ -0,0 +1,308 @@
+use std::{collections::{HashMap, HashSet}, error::Error, fs, path::Path, str::FromStr};
+use csv::ReaderBuilder;
+use encoding_rs::{UTF_8, WINDOWS_1252};
+
+use crate::codigos_postales;
+use crate::direccion;
+use crate::duplicados;
+use crate::entidad::{parse_fecha, parse_precio, Estado, Tipo, TipoVivienda, MONEDA_POR_DEFECTO};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Campo {
+    Identificacion,
+    Calle,
+    Numero,
+    Piso,
+    CodigoPostal,
+    MetrosCuadrados,
+    NumeroAseos,
+    NumeroHabitaciones,
+    Tipo,
+    Precio,
+    Moneda,
+    Estado,
+    FechaAlta,
+    Descripcion,
+    Municipio,
+    Provincia
+}
+
+impl Campo {
+    pub const TODOS: [Campo; 16] = [
+        Campo::Identificacion, Campo::Calle, Campo::Numero, Campo::Piso, Campo::CodigoPostal,
+        Campo::MetrosCuadrados, Campo::NumeroAseos, Campo::NumeroHabitaciones, Campo::Tipo,
+        Campo::Precio, Campo::Moneda, Campo::Estado, Campo::FechaAlta, Campo::Descripcion,
+        Campo::Municipio, Campo::Provincia
+    ];
+
+    pub fn nombre(&self) -> &'static str {
+        match self {
+            Campo::Identificacion => "Id",
+            Campo::Calle => "Calle",
+            Campo::Numero => "Numero",
+            Campo::Piso => "Piso",
+            Campo::CodigoPostal => "Código Postal",
+            Campo::MetrosCuadrados => "Metros",
+            Campo::NumeroAseos => "Aseos",
+            Campo::NumeroHabitaciones => "Habitaciones",
+            Campo::Tipo => "Tipo",
+            Campo::Precio => "Precio",
+            Campo::Moneda => "Moneda",
+            Campo::Estado => "Estado",
+            Campo::FechaAlta => "Fecha de alta",
+            Campo::Descripcion => "Descripción",
+            Campo::Municipio => "Municipio",
+            Campo::Provincia => "Provincia",
+        }
+    }
+
+    pub fn obligatorio(&self) -> bool {
+        matches!(self, Campo::Identificacion | Campo::Calle | Campo::Numero | Campo::CodigoPostal | Campo::MetrosCuadrados | Campo::Tipo)
+    }
+
+    // Cabeceras reconocidas ya normalizadas (minúsculas, sin tildes ni separadores)
+    fn alias(&self) -> &'static [&'static str] {
+        match self {
+            Campo::Identificacion => &["identificacion", "id", "codigo", "referencia", "ref"],
+            Campo::Calle => &["calle", "via", "direccion", "domicilio"],
+            Campo::Numero => &["numero", "num", "no", "portal"],
+            Campo::Piso => &["piso", "planta", "pisopuerta"],
+            Campo::CodigoPostal => &["codigopostal", "cp", "codpostal", "postal"],
+            Campo::MetrosCuadrados => &["metroscuadrados", "metros", "m2", "superficie"],
+            Campo::NumeroAseos => &["numeroaseos", "aseos", "banos", "numerobanos"],
+            Campo::NumeroHabitaciones => &["numerohabitaciones", "habitaciones", "dormitorios", "hab"],
+            Campo::Tipo => &["tipo", "tipovivienda", "clase"],
+            Campo::Precio => &["precio", "importe", "pvp", "alquiler"],
+            Campo::Moneda => &["moneda", "divisa"],
+            Campo::Estado => &["estado", "situacion", "disponibilidad"],
+            Campo::FechaAlta => &["fechaalta", "alta", "fecha", "fechapublicacion"],
+            Campo::Descripcion => &["descripcion", "observaciones", "comentarios", "notas"],
+            Campo::Municipio => &["municipio", "localidad", "poblacion", "ciudad"],
+            Campo::Provincia => &["provincia"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Codificacion {
+    Utf8,
+    Latin1
+}
+
+#[derive(Debug, Clone)]
+pub struct OpcionesImportacion {
+    pub delimitador: u8,
+    pub codificacion: Codificacion,
+    pub cabecera: bool,
+    pub mapeo: HashMap<Campo, usize>,
+}
+
+impl Default for OpcionesImportacion {
+    fn default() -> Self {
+        OpcionesImportacion { delimitador: b',', codificacion: Codificacion::Utf8, cabecera: true, mapeo: HashMap::new() }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct InformeImportacion {
+    pub aceptados: Vec<(usize, TipoVivienda)>,
+    pub rechazados: Vec<(usize, String)>,
+    pub duplicados: Vec<(usize, String)>,
+    // Filas aceptadas con la misma dirección que otra vivienda: solo es un aviso
+    pub misma_direccion: Vec<(usize, String)>,
+}
+
+fn normalize_header(cabecera: &str) -> String {
+    cabecera.to_lowercase().chars()
+        .map(|c| match c {
+            'á' | 'à' | 'ä' => 'a',
+            'é' | 'è' | 'ë' => 'e',
+            'í' | 'ì' | 'ï' => 'i',
+            'ó' | 'ò' | 'ö' | 'º' => 'o',
+            'ú' | 'ù' | 'ü' => 'u',
+            'ñ' => 'n',
+            '²' => '2',
+            otro => otro,
+        })
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect()
+}
+
+pub fn decode(bytes: &[u8], codificacion: Codificacion) -> Result<String, Box<dyn Error>> {
+    match codificacion {
+        Codificacion::Utf8 => {
+            let (texto, errores) = UTF_8.decode_with_bom_removal(bytes);
+            if errores {
+                return Err("El fichero no es UTF-8 válido; pruebe con Latin-1".into());
+            }
+            Ok(texto.into_owned())
+        }
+        Codificacion::Latin1 => Ok(WINDOWS_1252.decode_with_bom_removal(bytes).0.into_owned()),
+    }
+}
+
+// El separador más frecuente en la primera línea
+pub fn guess_delimiter(texto: &str) -> u8 {
+    let primera = texto.lines().next().unwrap_or("");
+    [b',', b';', b'\t', b'|']
+        .into_iter()
+        .max_by_key(|d| primera.bytes().filter(|b| b == d).count())
+        .unwrap_or(b',')
+}
+
+// Todas las filas del fichero, incluida la cabecera si la hay
+pub fn read(path: &Path, opciones: &OpcionesImportacion) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    let texto = decode(&fs::read(path)?, opciones.codificacion)?;
+    let mut rdr = ReaderBuilder::new()
+        .delimiter(opciones.delimitador)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(texto.as_bytes());
+    let mut filas = Vec::new();
+    for result in rdr.records() {
+        filas.push(result?.iter().map(|celda| celda.trim().to_string()).collect());
+    }
+    Ok(filas)
+}
+
+pub fn suggest_mapping(cabeceras: &[String]) -> HashMap<Campo, usize> {
+    let mut mapeo = HashMap::new();
+    for campo in Campo::TODOS {
+        let encontrada = cabeceras.iter().position(|cabecera| campo.alias().contains(&normalize_header(cabecera).as_str()));
+        if let Some(columna) = encontrada {
+            mapeo.insert(campo, columna);
+        }
+    }
+    mapeo
+}
+
+fn parse_tipo(valor: &str) -> Option<Tipo> {
+    let mut letras = valor.trim().chars();
+    let capitalizado = match letras.next() {
+        Some(primera) => primera.to_uppercase().chain(letras.flat_map(|c| c.to_lowercase())).collect::<String>(),
+        None => String::new(),
+    };
+    Tipo::from_str(&capitalizado).ok()
+}
+
+fn parse_estado(valor: &str) -> Option<Estado> {
+    let mut letras = valor.trim().chars();
+    let capitalizado = match letras.next() {
+        Some(primera) => primera.to_uppercase().chain(letras.flat_map(|c| c.to_lowercase())).collect::<String>(),
+        None => return Some(Estado::Disponible),
+    };
+    Estado::from_str(&capitalizado).ok()
+}
+
+fn parse_row(fila: &[String], mapeo: &HashMap<Campo, usize>) -> Result<TipoVivienda, String> {
+    let texto = |campo: Campo| -> Result<String, String> {
+        let valor = mapeo.get(&campo).and_then(|columna| fila.get(*columna)).cloned().unwrap_or_default();
+        if valor.is_empty() && campo.obligatorio() {
+            Err(format!("{} vacío", campo.nombre()))
+        } else {
+            Ok(valor)
+        }
+    };
+    let entero = |campo: Campo| -> Result<i32, String> {
+        let valor = texto(campo)?;
+        if valor.is_empty() {
+            return Ok(0);
+        }
+        valor.parse::<i32>().map_err(|_| format!("{} no es un número: '{}'", campo.nombre(), valor))
+    };
+    let tipo = texto(Campo::Tipo)?;
+    let precio = texto(Campo::Precio)?;
+    let moneda = texto(Campo::Moneda)?;
+    let estado = texto(Campo::Estado)?;
+    let mut tipo_vivienda = TipoVivienda {
+        identificacion: texto(Campo::Identificacion)?,
+        calle: texto(Campo::Calle)?,
+        numero: entero(Campo::Numero)?,
+        piso: texto(Campo::Piso)?,
+        codigo_postal: texto(Campo::CodigoPostal)?,
+        metros_cuadrados: entero(Campo::MetrosCuadrados)?,
+        numero_aseos: entero(Campo::NumeroAseos)?,
+        numero_habitaciones: entero(Campo::NumeroHabitaciones)?,
+        tipo: parse_tipo(&tipo).ok_or_else(|| format!("Tipo desconocido: '{}'", tipo))?,
+        precio: if precio.is_empty() { 0.0 } else { parse_precio(&precio)? },
+        moneda: if moneda.is_empty() { String::from(MONEDA_POR_DEFECTO) } else { moneda.to_uppercase() },
+        estado: parse_estado(&estado).ok_or_else(|| format!("Estado desconocido: '{}'", estado))?,
+        fecha_alta: parse_fecha(&texto(Campo::FechaAlta)?)?,
+        descripcion: texto(Campo::Descripcion)?,
+        municipio: texto(Campo::Municipio)?,
+        provincia: texto(Campo::Provincia)?
+    };
+    codigos_postales::check_format(&tipo_vivienda.codigo_postal).map_err(|aviso| aviso.to_string())?;
+    direccion::complete_provincia(&mut tipo_vivienda);
+    Ok(tipo_vivienda)
+}
+
+// Clasifica cada fila sin modificar nada; los números de fila son los del fichero
+pub fn analyze(filas: &[Vec<String>], opciones: &OpcionesImportacion, existentes: &[TipoVivienda]) -> Result<InformeImportacion, Box<dyn Error>> {
+    let sin_asignar: Vec<&str> = Campo::TODOS.iter()
+        .filter(|campo| campo.obligatorio() && !opciones.mapeo.contains_key(campo))
+        .map(|campo| campo.nombre())
+        .collect();
+    if !sin_asignar.is_empty() {
+        return Err(format!("Faltan columnas obligatorias: {}", sin_asignar.join(", ")).into());
+    }
+
+    let mut informe = InformeImportacion::default();
+    let mut vistos: HashSet<String> = existentes.iter().map(|e| e.identificacion.clone()).collect();
+    let inicio = if opciones.cabecera { 1 } else { 0 };
+    for (i, fila) in filas.iter().enumerate().skip(inicio) {
+        let numero_fila = i + 1;
+        if fila.iter().all(|celda| celda.is_empty()) {
+            continue;
+        }
+        match parse_row(fila, &opciones.mapeo) {
+            Ok(tipo_vivienda) if vistos.contains(&tipo_vivienda.identificacion) => {
+                informe.duplicados.push((numero_fila, tipo_vivienda.identificacion));
+            }
+            Ok(tipo_vivienda) => {
+                vistos.insert(tipo_vivienda.identificacion.clone());
+                let clave = duplicados::key(&tipo_vivienda);
+                let otra = existentes.iter().chain(informe.aceptados.iter().map(|(_, e)| e))
+                    .find(|e| e.identificacion != tipo_vivienda.identificacion && duplicados::key(e) == clave);
+                if let Some(otra) = otra {
+                    informe.misma_direccion.push((numero_fila, otra.identificacion.clone()));
+                }
+                informe.aceptados.push((numero_fila, tipo_vivienda));
+            }
+            Err(motivo) => informe.rechazados.push((numero_fila, motivo)),
+        }
+    }
+    Ok(informe)
+}
+
+#[test]
+fn suggest_mapping_cabeceras_en_castellano() {
+    let cabeceras: Vec<String> = vec!["Referencia", "Calle", "Nº", "Planta", "Código Postal", "m²", "Baños", "Dormitorios", "Tipo"]
+        .into_iter().map(String::from).collect();
+    let mapeo = suggest_mapping(&cabeceras);
+    assert_eq!(mapeo.get(&Campo::Identificacion), Some(&0));
+    assert_eq!(mapeo.get(&Campo::Numero), Some(&2));
+    assert_eq!(mapeo.get(&Campo::CodigoPostal), Some(&4));
+    assert_eq!(mapeo.get(&Campo::MetrosCuadrados), Some(&5));
+    assert_eq!(mapeo.get(&Campo::NumeroAseos), Some(&6));
+    assert_eq!(mapeo.len(), 9);
+}
+
+#[test]
+fn analyze_informe_por_fila() {
+    let filas: Vec<Vec<String>> = vec![
+        vec!["id", "calle", "numero", "cp", "metros", "tipo"],
+        vec!["1", "San Isidro", "4", "28350", "80", "apartamento"],
+        vec!["2", "Chile", "cuarenta", "28350", "100", "chalet"],
+        vec!["3", "Chile", "40", "28350", "100", "CHALET"],
+        vec!["3", "Chile", "42", "28350", "90", "casa"],
+        vec!["4", "C/ SAN ISIDRO", "4", "28350", "80", "apartamento"],
+    ].into_iter().map(|fila| fila.into_iter().map(String::from).collect()).collect();
+    let mut opciones = OpcionesImportacion::default();
+    opciones.mapeo = suggest_mapping(&filas[0]);
+    let informe = analyze(&filas, &opciones, &[]).unwrap();
+    assert_eq!(informe.aceptados.iter().map(|(fila, _)| *fila).collect::<Vec<_>>(), vec![2, 4, 6]);
+    assert_eq!(informe.rechazados, vec![(3, String::from("Numero no es un número: 'cuarenta'"))]);
+    assert_eq!(informe.duplicados, vec![(5, String::from("3"))]);
+    assert_eq!(informe.misma_direccion, vec![(6, String::from("1"))]);
+}
@@ -0,0 +1,237 @@
+use std::{collections::BTreeMap, error::Error, fs::File, io::BufWriter, path::Path};
+use chrono::Local;
+use printpdf::{BuiltinFont, IndirectFontRef, Line, Mm, PdfDocumentReference, PdfLayerReference, PdfDocument, Point};
+
+use crate::direccion::Direccion;
+use crate::entidad::TipoVivienda;
+
+const ANCHO_PAGINA: f32 = 210.0;
+const ALTO_PAGINA: f32 = 297.0;
+const MARGEN: f32 = 15.0;
+const INTERLINEA: f32 = 6.0;
+
+// Cabecera, posición x (mm) y máximo de caracteres de cada columna del catálogo
+const COLUMNAS: [(&str, f32, usize); 9] = [
+    ("Id", MARGEN, 8),
+    ("Calle", 33.0, 28),
+    ("Nº", 95.0, 5),
+    ("Piso", 106.0, 6),
+    ("C.P.", 121.0, 5),
+    ("m²", 138.0, 6),
+    ("Aseos", 151.0, 5),
+    ("Hab.", 166.0, 5),
+    ("Tipo", 180.0, 12),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Agrupacion {
+    Tipo,
+    CodigoPostal
+}
+
+struct Documento {
+    doc: PdfDocumentReference,
+    capa: PdfLayerReference,
+    normal: IndirectFontRef,
+    negrita: IndirectFontRef,
+    y: f32,
+}
+
+impl Documento {
+    fn new(titulo: &str) -> Result<Documento, Box<dyn Error>> {
+        let (doc, pagina, capa) = PdfDocument::new(titulo, Mm(ANCHO_PAGINA), Mm(ALTO_PAGINA), "Capa 1");
+        let normal = doc.add_builtin_font(BuiltinFont::Helvetica)?;
+        let negrita = doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
+        let capa = doc.get_page(pagina).get_layer(capa);
+        Ok(Documento { doc, capa, normal, negrita, y: ALTO_PAGINA - MARGEN })
+    }
+
+    // Salta de página si no queda sitio para alto milímetros más
+    fn ensure(&mut self, alto: f32) {
+        if self.y - alto < MARGEN {
+            let (pagina, capa) = self.doc.add_page(Mm(ANCHO_PAGINA), Mm(ALTO_PAGINA), "Capa 1");
+            self.capa = self.doc.get_page(pagina).get_layer(capa);
+            self.y = ALTO_PAGINA - MARGEN;
+        }
+    }
+
+    fn text(&mut self, texto: &str, tamano: f32, x: f32, negrita: bool) {
+        let fuente = if negrita { &self.negrita } else { &self.normal };
+        self.capa.use_text(texto, tamano, Mm(x), Mm(self.y), fuente);
+    }
+
+    fn line(&mut self, texto: &str, tamano: f32, negrita: bool) {
+        self.ensure(INTERLINEA);
+        self.text(texto, tamano, MARGEN, negrita);
+        self.y -= tamano * 0.5;
+    }
+
+    fn rule(&mut self) {
+        let puntos = vec![
+            (Point::new(Mm(MARGEN), Mm(self.y + INTERLINEA / 2.0 - 1.0)), false),
+            (Point::new(Mm(ANCHO_PAGINA - MARGEN), Mm(self.y + INTERLINEA / 2.0 - 1.0)), false),
+        ];
+        self.capa.add_line(Line { points: puntos, is_closed: false });
+    }
+
+    fn row(&mut self, celdas: &[String], negrita: bool) {
+        self.ensure(INTERLINEA);
+        for (celda, (_, x, maximo)) in celdas.iter().zip(COLUMNAS.iter()) {
+            let texto: String = celda.chars().take(*maximo).collect();
+            self.text(&texto, 9.0, *x, negrita);
+        }
+        if negrita {
+            self.rule();
+        }
+        self.y -= INTERLINEA;
+    }
+
+    fn skip(&mut self, alto: f32) {
+        self.y -= alto;
+    }
+
+    fn save(self, path: &Path) -> Result<(), Box<dyn Error>> {
+        self.doc.save(&mut BufWriter::new(File::create(path)?))?;
+        Ok(())
+    }
+}
+
+fn cells(p: &TipoVivienda) -> Vec<String> {
+    vec![
+        p.identificacion.clone(),
+        p.calle.clone(),
+        p.numero.to_string(),
+        p.piso.clone(),
+        p.codigo_postal.clone(),
+        p.metros_cuadrados.to_string(),
+        p.numero_aseos.to_string(),
+        p.numero_habitaciones.to_string(),
+        p.tipo.to_string(),
+    ]
+}
+
+// Reparte el texto en líneas de como mucho maximo caracteres sin cortar palabras
+fn wrap(texto: &str, maximo: usize) -> Vec<String> {
+    let mut lineas = Vec::new();
+    let mut actual = String::new();
+    for palabra in texto.split_whitespace() {
+        if !actual.is_empty() && actual.chars().count() + 1 + palabra.chars().count() > maximo {
+            lineas.push(std::mem::take(&mut actual));
+        }
+        if !actual.is_empty() {
+            actual.push(' ');
+        }
+        actual.push_str(palabra);
+    }
+    if !actual.is_empty() {
+        lineas.push(actual);
+    }
+    lineas
+}
+
+pub fn catalogue(path: &Path, datos: &[TipoVivienda], agrupacion: Agrupacion) -> Result<(), Box<dyn Error>> {
+    let mut grupos: BTreeMap<String, Vec<&TipoVivienda>> = BTreeMap::new();
+    for p in datos {
+        let clave = match agrupacion {
+            Agrupacion::Tipo => p.tipo.to_string(),
+            Agrupacion::CodigoPostal => p.codigo_postal.clone(),
+        };
+        grupos.entry(clave).or_default().push(p);
+    }
+
+    let mut documento = Documento::new("Catálogo de viviendas")?;
+    documento.line("Catálogo de viviendas", 18.0, true);
+    documento.line(&format!("{} viviendas - {}", datos.len(), Local::now().format("%d/%m/%Y %H:%M")), 10.0, false);
+    documento.skip(INTERLINEA);
+
+    let cabecera: Vec<String> = COLUMNAS.iter().map(|(nombre, _, _)| nombre.to_string()).collect();
+    let etiqueta = match agrupacion {
+        Agrupacion::Tipo => "Tipo",
+        Agrupacion::CodigoPostal => "Código postal",
+    };
+    for (clave, mut grupo) in grupos {
+        grupo.sort_by(|a, b| a.identificacion.cmp(&b.identificacion));
+        // Cabecera del grupo y al menos una fila en la misma página
+        documento.ensure(INTERLINEA * 4.0);
+        documento.line(&format!("{}: {} ({})", etiqueta, clave, grupo.len()), 12.0, true);
+        documento.row(&cabecera, true);
+        for p in grupo {
+            documento.row(&cells(p), false);
+        }
+        documento.skip(INTERLINEA / 2.0);
+    }
+    documento.save(path)
+}
+
+pub fn fact_sheet(path: &Path, p: &TipoVivienda) -> Result<(), Box<dyn Error>> {
+    let mut documento = Documento::new(&format!("Ficha de vivienda {}", p.identificacion))?;
+    let direccion = Direccion::from_tipo_vivienda(p);
+    documento.line(&format!("{} {}, {}", direccion.calle(), p.numero, direccion.piso_puerta()), 20.0, true);
+    documento.line(&format!("{} {} - {}", p.codigo_postal, p.municipio, p.tipo), 14.0, false);
+    documento.skip(INTERLINEA);
+
+    let campos = [
+        ("Referencia", p.identificacion.clone()),
+        ("Tipo", p.tipo.to_string()),
+        ("Calle", p.calle.clone()),
+        ("Número", p.numero.to_string()),
+        ("Piso", p.piso.clone()),
+        ("Código postal", p.codigo_postal.clone()),
+        ("Municipio", p.municipio.clone()),
+        ("Provincia", p.provincia.clone()),
+        ("Superficie", format!("{} m²", p.metros_cuadrados)),
+        ("Habitaciones", p.numero_habitaciones.to_string()),
+        ("Aseos", p.numero_aseos.to_string()),
+        ("Precio", format!("{:.2} {}", p.precio, p.moneda)),
+        ("Estado", p.estado.to_string()),
+        ("Fecha de alta", p.fecha_alta.clone()),
+    ];
+    for (nombre, valor) in campos {
+        documento.ensure(INTERLINEA * 1.5);
+        documento.text(nombre, 11.0, MARGEN, true);
+        documento.text(&valor, 11.0, MARGEN + 45.0, false);
+        documento.skip(INTERLINEA * 1.5);
+    }
+    if !p.descripcion.is_empty() {
+        documento.skip(INTERLINEA);
+        documento.line("Descripción", 11.0, true);
+        for linea in wrap(&p.descripcion, 95) {
+            documento.line(&linea, 10.0, false);
+        }
+    }
+    documento.skip(INTERLINEA);
+    documento.line(&format!("Generado el {}", Local::now().format("%d/%m/%Y %H:%M")), 8.0, false);
+    documento.save(path)
+}
+
+#[cfg(test)]
+fn check_pdf(nombre: &str, genera: impl FnOnce(&Path) -> Result<(), Box<dyn Error>>) {
+    let path = std::env::temp_dir().join(format!("{}-{}.pdf", nombre, std::process::id()));
+    genera(&path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(bytes.starts_with(b"%PDF"));
+}
+
+#[test]
+fn catalogo_por_tipo_y_codigo_postal() {
+    let datos = vec![
+        TipoVivienda::example("1"),
+        TipoVivienda { codigo_postal: String::from("28001"), tipo: crate::entidad::Tipo::Chalet, ..TipoVivienda::example("2") },
+    ];
+    check_pdf("catalogo-tipo", |path| catalogue(path, &datos, Agrupacion::Tipo));
+    check_pdf("catalogo-codigo-postal", |path| catalogue(path, &datos, Agrupacion::CodigoPostal));
+}
+
+#[test]
+fn catalogo_vacio_y_de_varias_paginas() {
+    check_pdf("catalogo-vacio", |path| catalogue(path, &[], Agrupacion::Tipo));
+    let datos: Vec<TipoVivienda> = (1..=150).map(|i| TipoVivienda::example(&i.to_string())).collect();
+    check_pdf("catalogo-largo", |path| catalogue(path, &datos, Agrupacion::CodigoPostal));
+}
+
+#[test]
+fn ficha_de_vivienda() {
+    let tipo_vivienda = TipoVivienda { descripcion: "Luminoso, reformado y cerca del metro. ".repeat(10), ..TipoVivienda::example("1") };
+    check_pdf("ficha", |path| fact_sheet(path, &tipo_vivienda));
+}
@@ -9,8 +9,31 @@ mod entidad;
 mod schema;
 mod repository;
 mod presentacion;
+mod configuracion;
+mod cli;
+mod importacion;
+mod exportacion;
+mod informe;
+mod estadisticas;
+mod direccion;
+mod codigos_postales;
+mod duplicados;
+mod versiones;
+mod copias;
+mod fusion;
+mod tareas;
+mod busqueda;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        if let Err(e) = cli::run(&args) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let mut tipoViviendaDAO = TipoViviendaDAO::new();
     let mut gui = presentacion::GUI::new();
     gui.build();
@@ -1,4 +1,4 @@
-use std::{io::SeekFrom, str::FromStr};
+use std::{cell::RefCell, collections::VecDeque, io::SeekFrom, rc::Rc, str::FromStr, path::{Path, PathBuf}, sync::{Arc, Mutex}};
 
 use fltk::{
     app::{self, App}, enums,
@@ -8,13 +8,29 @@ use fltk::{
 use fltk_table::{SmartTable, TableOpts};
 
 use fltk::{app::*, browser::*, button::*, enums::*, input::*, prelude::*, window::*};
+use fltk::{frame::Frame, group::Flex, misc::Progress};
+use fltk::{dialog::{self, NativeFileChooser, NativeFileChooserOptions, NativeFileChooserType}, menu::{MenuBar, MenuFlag}};
 use serde::__private::de;
 
+mod papelera;
+mod asistente_importacion;
+mod estadisticas;
+mod graficos;
+mod duplicados;
+mod historial;
+mod copias;
+mod fusion;
+
 const WIDGET_WIDTH: i32 = 70;
 const WIDGET_HEIGHT: i32 = 25;
 const WIDGET_PADDING: i32 = 10;
+// Cada cuántos segundos se comprueba si otro programa ha cambiado el CSV o la base de datos
+const INTERVALO_VIGILANCIA: f64 = 2.0;
+const FILTRO_DATOS: &str = "JSON\t*.json\nJSON Lines\t*.{jsonl,ndjson}\nCSV\t*.csv";
+const FILTRO_PDF: &str = "PDF\t*.pdf";
+const FILTRO_EXPORTAR: &str = "JSON\t*.json\nJSON Lines\t*.{jsonl,ndjson}\nCSV\t*.csv\nExcel\t*.xlsx\nOpenDocument\t*.ods";
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 enum Message {
     Create,
     Update,
@@ -22,14 +38,78 @@ enum Message {
     Select,
     Filter,
     Save,
+    New,
+    Open,
+    OpenRecent(usize),
+    Trash,
+    Backup,
+    ExternalChange,
+    RestoreBackup,
+    Import,
+    ImportWizard,
+    Export,
+    ExportFiltered,
+    Report(Agrupacion),
+    FactSheet,
+    History,
+    Statistics,
+    Charts,
+    Duplicates,
+    Normalize,
+    PostalCode,
+    BulkDelete,
+    BulkTipo,
+    BulkCodigoPostal,
+    ExportSelection,
+    Cancel,
+    Task(Evento),
 }
 
 //use crate::entidad::{Persona, ScreenOutput};
 //use crate::entidad::PersonaDAO;
 
-use crate::entidad::{TipoVivienda, ScreenOutput};
+use crate::entidad::{ResumenGuardado, TipoVivienda, ScreenOutput};
 use crate::entidad::TipoViviendaDAO;
-use crate::entidad::Tipo;
+use crate::repository::ConflictoVersion;
+use crate::entidad::{parse_fecha, parse_precio, today, Estado, Tipo, MONEDA_POR_DEFECTO};
+use crate::configuracion::{Configuracion, Ventana};
+use crate::informe::{self, Agrupacion};
+use crate::estadisticas::Estadisticas;
+use crate::direccion::{self, Direccion};
+use crate::codigos_postales::{self, CatalogoCodigosPostales};
+use crate::tareas::{Control, Evento, Trabajador, Trabajo};
+use crate::busqueda;
+
+// '/' y '\' separan submenús en FLTK y '&' marca el atajo: hay que escaparlos
+fn menu_label(texto: &str) -> String {
+    texto.replace('\\', "\\\\").replace('/', "\\/").replace('&', "&&")
+}
+
+// Cola de acciones de una ventana modal. No se usa app::channel porque su cola es la misma para toda la
+// aplicación: la ventana modal recibiría y descartaría los mensajes de la principal, como los de los
+// temporizadores o el resultado de una tarea en segundo plano
+struct Acciones<T>(Rc<RefCell<VecDeque<T>>>);
+
+impl<T: Copy + 'static> Acciones<T> {
+    fn new() -> Acciones<T> {
+        Acciones(Rc::new(RefCell::new(VecDeque::new())))
+    }
+
+    fn emit<W: WidgetExt>(&self, widget: &mut W, accion: T) {
+        let acciones = Acciones(self.0.clone());
+        widget.set_callback(move |_| acciones.send(accion));
+    }
+
+    // Como Sender::send, despierta a app::wait para que la acción se atienda sin esperar a otro evento
+    fn send(&self, accion: T) {
+        self.0.borrow_mut().push_back(accion);
+        app::awake();
+    }
+
+    fn recv(&self) -> Option<T> {
+        self.0.borrow_mut().pop_front()
+    }
+}
 
 pub struct GUI{
     app : App,
@@ -37,117 +117,157 @@ pub struct GUI{
     sender : Sender<Message>,
     receiver : Receiver<Message>,
     model : Vec<TipoVivienda>,
-    tipoViviendaDAO : TipoViviendaDAO,
+    // Compartido con el hilo que hace los guardados, importaciones y exportaciones
+    tipoViviendaDAO : Arc<Mutex<TipoViviendaDAO>>,
+    trabajador : Trabajador,
+    tarea : Option<Control>,
+    configuracion : Configuracion,
+    codigos_postales : CatalogoCodigosPostales,
+    menu : MenuBar,
+    status_bar : Frame,
+    progress_bar : Progress,
+    cancel_button : Button,
+    visibles : Vec<usize>,
+    graficos : Option<graficos::Graficos>,
+    dirty : bool,
+    ultimo_mensaje : String,
+    ultimo_error : bool,
     filter_input : Input,
-    list_browser : HoldBrowser,
+    list_browser : MultiBrowser,
     ident_input : Input,
     calle_input : Input,
     numero_input : Input,
     piso_input : Input,
     codigo_postal_input : Input,
+    municipio_input : Input,
+    provincia_input : Input,
     metros_cuadrados_input : Input,
     numero_aseos_input: Input,
     numero_habitaciones_input: Input,
     tipo_input: Input, 
+    precio_input: Input,
+    moneda_input: Input,
+    estado_input: Input,
+    fecha_alta_input: Input,
+    descripcion_input: Input,
     create_button : Button,
     update_button : Button,
     delete_button : Button,
-    save_button : Button
+    save_button : Button,
+    normalize_button : Button
 }
 
 impl GUI {
     
     pub fn new() -> GUI {
         let mut app = app::App::default().with_scheme(app::Scheme::Gtk);
-        let mut wind = Window::default().with_label("CRUD");
+        let configuracion = Configuracion::load();
+        let mut wind = Window::default().with_size(800, 780).with_label("CRUD");
+        if let Some(ventana) = &configuracion.ventana {
+            wind.resize(ventana.x, ventana.y, ventana.w, ventana.h);
+        }
         let (sender, receiver) = channel::<Message>();
 
-        let mut filter_input = Input::default().with_size(WIDGET_WIDTH, WIDGET_HEIGHT)
-        .with_pos(WIDGET_PADDING + WIDGET_WIDTH * 2, WIDGET_PADDING)
-        .with_label("Filter prefix:");
-
-        let mut list_browser = HoldBrowser::default().with_pos(
-            WIDGET_PADDING,
-            filter_input.y() + filter_input.height() + WIDGET_PADDING,
-        )
-        .with_size(WIDGET_WIDTH * 3, WIDGET_HEIGHT * 4);
-
-        let ident_input = Input::default()
-        .with_size(WIDGET_WIDTH, WIDGET_HEIGHT)
-        .with_pos(
-            list_browser.x() + list_browser.width() + WIDGET_PADDING + WIDGET_WIDTH,
-            list_browser.y(),
-        )
-        .with_label("Id:");
-
-        let calle_input = Input::default()
-        .with_size(WIDGET_WIDTH*3, WIDGET_HEIGHT)
-        .below_of(&ident_input, WIDGET_PADDING)
-        .with_label("Calle:");
-
-        let numero_input = Input::default()
-        .with_size(WIDGET_WIDTH, WIDGET_HEIGHT)
-        .below_of(&calle_input, WIDGET_PADDING)
-        .with_label("Numero:");
-
-        let piso_input = Input::default()
-        .with_size(WIDGET_WIDTH, WIDGET_HEIGHT)
-        .below_of(&numero_input, WIDGET_PADDING)
-        .with_label("Piso:");
-
-        let codigo_postal_input = Input::default()
-        .with_size(WIDGET_WIDTH, WIDGET_HEIGHT)
-        .below_of(&piso_input, WIDGET_PADDING)
-        .with_label("Código Postal:");
-
-        let metros_cuadrados_input = Input::default()
-        .with_size(WIDGET_WIDTH, WIDGET_HEIGHT)
-        .below_of(&codigo_postal_input, WIDGET_PADDING)
-        .with_label("Metros:");
-
-        let numero_aseos_input = Input::default()
-        .with_size(WIDGET_WIDTH, WIDGET_HEIGHT)
-        .below_of(&metros_cuadrados_input, WIDGET_PADDING)
-        .with_label("Aseos:");
-
-        let numero_habitaciones_input = Input::default()
-        .with_size(WIDGET_WIDTH, WIDGET_HEIGHT)
-        .below_of(&numero_aseos_input, WIDGET_PADDING)
-        .with_label("Habitaciones:");
-
-        let tipo_input = Input::default()
-        .with_size(WIDGET_WIDTH*3, WIDGET_HEIGHT)
-        .below_of(&numero_habitaciones_input, WIDGET_PADDING)
-        .with_label("Tipo:");
-
-
-
-
-        let mut create_button = Button::default()
-            .with_size(WIDGET_WIDTH, WIDGET_HEIGHT)
-            .with_pos(
-                WIDGET_PADDING,
-                list_browser.y() + list_browser.height() + WIDGET_PADDING*25,
-            )
-            .with_label("Crear");
-
-        let mut update_button = Button::default()
-            .with_size(WIDGET_WIDTH, WIDGET_HEIGHT)
-            .right_of(&create_button, WIDGET_PADDING)
-            .with_label("Modificar");
-
-        let mut delete_button = Button::default()
-            .with_size(WIDGET_WIDTH, WIDGET_HEIGHT)
-            .right_of(&update_button, WIDGET_PADDING)
-            .with_label("Borrar");
-
-        let mut save_button = Button::default()
-            .with_size(WIDGET_WIDTH, WIDGET_HEIGHT)
-            .right_of(&delete_button, WIDGET_PADDING)
-            .with_label("Guardar");
-
-        let tipoViviendaDAO = TipoViviendaDAO::new();
+        let mut principal = Flex::default_fill().column();
+
+        let menu = MenuBar::default();
+        principal.set_size(&menu, WIDGET_HEIGHT);
+
+        let mut contenido = Flex::default().column();
+        contenido.set_margin(WIDGET_PADDING);
+        contenido.set_pad(WIDGET_PADDING);
+
+        let mut fila_filtro = Flex::default().row();
+        let filter_label = Frame::default().with_label("Buscar:").with_align(Align::Right | Align::Inside);
+        fila_filtro.set_size(&filter_label, WIDGET_WIDTH * 2);
+        let mut filter_input = Input::default();
+        filter_input.set_tooltip("Palabras de cualquier campo, por ejemplo: san isidro chalet 28350");
+        fila_filtro.set_size(&filter_input, WIDGET_WIDTH * 6);
+        Frame::default();
+        fila_filtro.end();
+        contenido.set_size(&fila_filtro, WIDGET_HEIGHT);
+
+        let mut fila_datos = Flex::default().row();
+        fila_datos.set_pad(WIDGET_PADDING);
+
+        let mut list_browser = MultiBrowser::default();
+
+        let mut formulario = Flex::default().column();
+        formulario.set_pad(WIDGET_PADDING);
+        let ident_input = GUI::form_row(&mut formulario, "Id:");
+        let calle_input = GUI::form_row(&mut formulario, "Calle:");
+        let numero_input = GUI::form_row(&mut formulario, "Numero:");
+        let piso_input = GUI::form_row(&mut formulario, "Piso:");
+        let codigo_postal_input = GUI::form_row(&mut formulario, "Código Postal:");
+        let municipio_input = GUI::form_row(&mut formulario, "Municipio:");
+        let provincia_input = GUI::form_row(&mut formulario, "Provincia:");
+        let metros_cuadrados_input = GUI::form_row(&mut formulario, "Metros:");
+        let numero_aseos_input = GUI::form_row(&mut formulario, "Aseos:");
+        let numero_habitaciones_input = GUI::form_row(&mut formulario, "Habitaciones:");
+        let tipo_input = GUI::form_row(&mut formulario, "Tipo:");
+        let precio_input = GUI::form_row(&mut formulario, "Precio:");
+        let moneda_input = GUI::form_row(&mut formulario, "Moneda:");
+        let estado_input = GUI::form_row(&mut formulario, "Estado:");
+        let fecha_alta_input = GUI::form_row(&mut formulario, "Fecha alta:");
+        let descripcion_input = GUI::form_row(&mut formulario, "Descripción:");
+        Frame::default();
+        formulario.end();
+        fila_datos.set_size(&formulario, WIDGET_WIDTH * 5);
+
+        fila_datos.end();
+
+        let mut fila_botones = Flex::default().row();
+        fila_botones.set_pad(WIDGET_PADDING);
+
+        let mut create_button = Button::default().with_label("Crear");
+        fila_botones.set_size(&create_button, WIDGET_WIDTH);
+
+        let mut update_button = Button::default().with_label("Modificar");
+        fila_botones.set_size(&update_button, WIDGET_WIDTH);
+
+        let mut delete_button = Button::default().with_label("Borrar");
+        fila_botones.set_size(&delete_button, WIDGET_WIDTH);
+
+        let mut save_button = Button::default().with_label("Guardar");
+        fila_botones.set_size(&save_button, WIDGET_WIDTH);
+
+        let mut normalize_button = Button::default().with_label("Normalizar");
+        fila_botones.set_size(&normalize_button, WIDGET_WIDTH + WIDGET_PADDING * 2);
+
+        Frame::default();
+        fila_botones.end();
+        contenido.set_size(&fila_botones, WIDGET_HEIGHT);
+
+        contenido.end();
+
+        let mut fila_estado = Flex::default().row();
+        let mut status_bar = Frame::default().with_align(Align::Left | Align::Inside);
+        status_bar.set_frame(FrameType::ThinDownBox);
+        let mut progress_bar = Progress::default();
+        progress_bar.set_minimum(0.0);
+        progress_bar.set_maximum(100.0);
+        progress_bar.set_selection_color(Color::Blue);
+        fila_estado.set_size(&progress_bar, WIDGET_WIDTH * 3);
+        let cancel_button = Button::default().with_label("Cancelar");
+        fila_estado.set_size(&cancel_button, WIDGET_WIDTH + WIDGET_PADDING * 2);
+        fila_estado.end();
+        principal.set_size(&fila_estado, WIDGET_HEIGHT);
+
+        principal.end();
+
+        wind.size_range(WIDGET_WIDTH * 9, WIDGET_HEIGHT * 28, 0, 0);
+        wind.resizable(&principal);
+
+        let mut tipoViviendaDAO = TipoViviendaDAO::new();
+        tipoViviendaDAO.enable_backups(configuracion.copias.clone());
         let model = tipoViviendaDAO.asVector();
+        let tipoViviendaDAO = Arc::new(Mutex::new(tipoViviendaDAO));
+        let trabajador = Trabajador::new(tipoViviendaDAO.clone(), move |evento| sender.send(Message::Task(evento)));
+        // Un catálogo local ilegible no impide arrancar: se usa el incluido y se avisa en la barra de estado
+        let (codigos_postales, ultimo_mensaje) = match CatalogoCodigosPostales::load(configuracion.codigos_postales.as_deref()) {
+            Ok(catalogo) => (catalogo, String::new()),
+            Err(e) => (CatalogoCodigosPostales::bundled(), e.to_string()),
+        };
 
         GUI {
             app : app,
@@ -157,24 +277,47 @@ impl GUI {
             filter_input : filter_input,
             list_browser : list_browser,
             tipoViviendaDAO : tipoViviendaDAO,
+            trabajador : trabajador,
+            tarea : None,
             model : model,
+            codigos_postales : codigos_postales,
+            configuracion : configuracion,
+            menu : menu,
+            status_bar : status_bar,
+            progress_bar : progress_bar,
+            cancel_button : cancel_button,
+            visibles : Vec::new(),
+            graficos : None,
+            dirty : false,
+            ultimo_error : !ultimo_mensaje.is_empty(),
+            ultimo_mensaje : ultimo_mensaje,
             ident_input : ident_input,
             calle_input : calle_input,
             numero_input : numero_input,
             piso_input: piso_input,
             codigo_postal_input: codigo_postal_input,
+            municipio_input: municipio_input,
+            provincia_input: provincia_input,
             metros_cuadrados_input: metros_cuadrados_input,
             numero_aseos_input: numero_aseos_input,
             numero_habitaciones_input: numero_habitaciones_input,
             tipo_input: tipo_input,
+            precio_input: precio_input,
+            moneda_input: moneda_input,
+            estado_input: estado_input,
+            fecha_alta_input: fecha_alta_input,
+            descripcion_input: descripcion_input,
             create_button : create_button,
             update_button : update_button,
             delete_button : delete_button,
-            save_button : save_button
+            save_button : save_button,
+            normalize_button : normalize_button
         }
     }
 
     pub fn build(&mut self) {
+        self.build_menu();
+
         self.filter_input.set_trigger(CallbackTrigger::Changed);
         self.filter_input.emit(self.sender, Message::Filter);
 
@@ -192,156 +335,896 @@ impl GUI {
 
         self.save_button.emit(self.sender, Message::Save);
 
-        self.wind.set_size(
-            self.calle_input.x() + self.numero_input.width() + self.piso_input.width() + self.codigo_postal_input.width() + WIDGET_PADDING,
-            self.create_button.y() + self.create_button.height() + WIDGET_PADDING * 20,
-        );
+        self.normalize_button.emit(self.sender, Message::Normalize);
+
+        self.cancel_button.emit(self.sender, Message::Cancel);
+        self.cancel_button.deactivate();
+
+        self.codigo_postal_input.set_trigger(CallbackTrigger::Changed);
+        self.codigo_postal_input.emit(self.sender, Message::PostalCode);
 
         self.sender.send(Message::Filter);
 
     }
 
+    fn form_row(formulario: &mut Flex, etiqueta: &str) -> Input {
+        let mut fila = Flex::default().row();
+        let label = Frame::default().with_label(etiqueta).with_align(Align::Right | Align::Inside);
+        fila.set_size(&label, WIDGET_WIDTH + WIDGET_PADDING * 2);
+        let input = Input::default();
+        fila.end();
+        formulario.set_size(&fila, WIDGET_HEIGHT);
+        input
+    }
+
+    fn build_menu(&mut self) {
+        self.menu.clear();
+        self.menu.add_emit("&Archivo/&Nuevo...\t", Shortcut::Ctrl | 'n', MenuFlag::Normal, self.sender, Message::New);
+        self.menu.add_emit("&Archivo/&Abrir...\t", Shortcut::Ctrl | 'o', MenuFlag::Normal, self.sender, Message::Open);
+        if self.configuracion.recientes.is_empty() {
+            self.menu.add("&Archivo/&Recientes/(vacío)\t", Shortcut::None, MenuFlag::Inactive, |_| {});
+        }
+        for (i, path) in self.configuracion.recientes.iter().enumerate() {
+            self.menu.add_emit(&format!("&Archivo/&Recientes/{}\t", menu_label(path)), Shortcut::None, MenuFlag::Normal, self.sender, Message::OpenRecent(i));
+        }
+        self.menu.add_emit("&Archivo/&Importar...\t", Shortcut::Ctrl | 'i', MenuFlag::Normal, self.sender, Message::Import);
+        self.menu.add_emit("&Archivo/Importar CSV con &asistente...\t", Shortcut::None, MenuFlag::Normal, self.sender, Message::ImportWizard);
+        self.menu.add_emit("&Archivo/&Exportar...\t", Shortcut::Ctrl | 'e', MenuFlag::Normal, self.sender, Message::Export);
+        self.menu.add_emit("&Archivo/Exportar &filtradas...\t", Shortcut::None, MenuFlag::Normal, self.sender, Message::ExportFiltered);
+        self.menu.add_emit("&Archivo/&Papelera...\t", Shortcut::None, MenuFlag::Normal, self.sender, Message::Trash);
+        self.menu.add_emit("&Archivo/&Copia de seguridad ahora\t", Shortcut::None, MenuFlag::Normal, self.sender, Message::Backup);
+        self.menu.add_emit("&Archivo/Restaurar copia de &seguridad...\t", Shortcut::None, MenuFlag::Normal, self.sender, Message::RestoreBackup);
+        self.menu.add_emit("&Informes/Catálogo por &tipo...\t", Shortcut::None, MenuFlag::Normal, self.sender, Message::Report(Agrupacion::Tipo));
+        self.menu.add_emit("&Informes/Catálogo por &código postal...\t", Shortcut::None, MenuFlag::Normal, self.sender, Message::Report(Agrupacion::CodigoPostal));
+        self.menu.add_emit("&Informes/&Ficha de la vivienda seleccionada...\t", Shortcut::None, MenuFlag::Normal, self.sender, Message::FactSheet);
+        self.menu.add_emit("&Informes/&Historial de la vivienda seleccionada...\t", Shortcut::None, MenuFlag::Normal, self.sender, Message::History);
+        self.menu.add_emit("&Informes/&Estadísticas...\t", Shortcut::None, MenuFlag::Normal, self.sender, Message::Statistics);
+        self.menu.add_emit("&Informes/&Gráficos...\t", Shortcut::None, MenuFlag::Normal, self.sender, Message::Charts);
+        self.menu.add_emit("&Informes/Posibles &duplicados...\t", Shortcut::None, MenuFlag::Normal, self.sender, Message::Duplicates);
+        self.menu.add_emit("&Selección/&Borrar...\t", Shortcut::None, MenuFlag::Normal, self.sender, Message::BulkDelete);
+        self.menu.add_emit("&Selección/Cambiar &tipo...\t", Shortcut::None, MenuFlag::Normal, self.sender, Message::BulkTipo);
+        self.menu.add_emit("&Selección/Cambiar &código postal...\t", Shortcut::None, MenuFlag::Normal, self.sender, Message::BulkCodigoPostal);
+        self.menu.add_emit("&Selección/&Exportar...\t", Shortcut::None, MenuFlag::Normal, self.sender, Message::ExportSelection);
+    }
+
+    fn choose_database(&mut self, tipo: NativeFileChooserType, titulo: &str) -> Option<PathBuf> {
+        self.choose_file(tipo, titulo, "SQLite\t*.{sqlite,db}")
+    }
+
+    fn choose_file(&mut self, tipo: NativeFileChooserType, titulo: &str, filtro: &str) -> Option<PathBuf> {
+        let mut dialogo = NativeFileChooser::new(tipo);
+        dialogo.set_title(titulo);
+        dialogo.set_filter(filtro);
+        dialogo.set_option(NativeFileChooserOptions::SaveAsConfirm);
+        dialogo.show();
+        let path = dialogo.filename();
+        if path.as_os_str().is_empty() {
+            None
+        } else {
+            Some(path)
+        }
+    }
+
+    fn export(&mut self, path: &Path, datos: &[TipoVivienda]) {
+        let mut path = path.to_path_buf();
+        if path.extension().is_none() {
+            path.set_extension("json");
+        }
+        self.start_task(Trabajo::Export(path, datos.to_vec()));
+    }
+
+    fn start_task(&mut self, trabajo: Trabajo) {
+        if self.tarea.is_some() {
+            self.set_status("Espere a que termine la tarea en curso", true);
+            return;
+        }
+        self.progress_bar.set_value(0.0);
+        self.progress_bar.set_label(trabajo.nombre());
+        if trabajo.cancellable() {
+            self.cancel_button.activate();
+        }
+        self.save_button.deactivate();
+        self.set_status(&format!("{}...", trabajo.nombre()), false);
+        self.tarea = Some(self.trabajador.submit(trabajo));
+    }
+
+    fn finish_task(&mut self) {
+        self.tarea = None;
+        self.progress_bar.set_value(0.0);
+        self.progress_bar.set_label("");
+        self.cancel_button.deactivate();
+        self.save_button.activate();
+    }
+
+    // Posiciones en model de las viviendas que encajan con el buscador, las más parecidas primero.
+    // Con cambios sin guardar o con la base de datos ocupada por una tarea se busca solo en memoria
+    fn search(&self, texto: &str) -> Vec<usize> {
+        if texto.trim().is_empty() {
+            return (0..self.model.len()).collect();
+        }
+        if !self.dirty {
+            if let Ok(mut tipoViviendaDAO) = self.tipoViviendaDAO.try_lock() {
+                if let Ok(encontradas) = tipoViviendaDAO.search(texto) {
+                    return encontradas.iter()
+                        .filter_map(|encontrada| self.model.iter().position(|e| e.identificacion == encontrada.identificacion))
+                        .collect();
+                }
+            }
+        }
+        busqueda::rank(&self.model, texto)
+    }
+
+    fn add_imported(&mut self, path: &Path, importadas: Vec<TipoVivienda>) {
+        let total = importadas.len();
+        let mut misma_direccion = 0;
+        for tipoVivienda in importadas {
+            if !crate::duplicados::find_matches(&tipoVivienda, &self.model).is_empty() {
+                misma_direccion += 1;
+            }
+            match self.model.iter_mut().find(|e| e.identificacion == tipoVivienda.identificacion) {
+                Some(existente) => *existente = tipoVivienda,
+                None => self.model.push(tipoVivienda),
+            }
+        }
+        self.dirty = true;
+        if misma_direccion > 0 {
+            self.set_status(&format!("Importadas {} viviendas de {}. Atención: {} con la misma dirección que otra, revise Informes > Posibles duplicados",
+                total, path.display(), misma_direccion), true);
+        } else {
+            self.set_status(&format!("Importadas {} viviendas de {}", total, path.display()), false);
+        }
+        self.sender.send(Message::Filter);
+    }
+
+    // Si el guardado falla se conservan el modelo y el formulario para no perder los cambios
+    fn saved(&mut self, resultado: Result<ResumenGuardado, String>) {
+        match resultado {
+            Ok(resumen) => {
+                self.model = self.tipoViviendaDAO.lock().unwrap().asVector();
+                self.dirty = false;
+                self.clear_edit();
+                self.set_status(&resumen.to_string(), false);
+                self.sender.send(Message::Filter);
+                self.sender.send(Message::Select);
+            }
+            Err(e) => self.set_status(&format!("Error al guardar: {}", e), true),
+        }
+    }
+
+    fn open_database(&mut self, path: &Path) {
+        match TipoViviendaDAO::open(&path.to_string_lossy()) {
+            Ok(tipoViviendaDAO) => {
+                *self.tipoViviendaDAO.lock().unwrap() = tipoViviendaDAO;
+                self.tipoViviendaDAO.lock().unwrap().enable_backups(self.configuracion.copias.clone());
+                self.model = self.tipoViviendaDAO.lock().unwrap().asVector();
+                self.dirty = false;
+                self.configuracion.add_reciente(&path.to_string_lossy());
+                self.build_menu();
+                self.wind.set_label(&format!("CRUD - {}", path.display()));
+                self.clear_edit();
+                match self.configuracion.save() {
+                    Ok(()) => self.set_status(&format!("Abierto {}", path.display()), false),
+                    Err(e) => self.set_status(&format!("No se pudo guardar la configuración: {}", e), true),
+                }
+                self.sender.send(Message::Filter);
+            }
+            Err(e) => {
+                self.set_status(&format!("No se pudo abrir {}: {}", path.display(), e), true);
+            }
+        }
+    }
+
     fn clear_edit(&mut self) {
         self.ident_input.set_value("");
         self.calle_input.set_value("");
         self.numero_input.set_value("");
         self.piso_input.set_value("");
         self.codigo_postal_input.set_value("");
+        self.municipio_input.set_value("");
+        self.provincia_input.set_value("");
         self.metros_cuadrados_input.set_value("");
         self.numero_aseos_input.set_value("");
         self.numero_habitaciones_input.set_value("");
         self.tipo_input.set_value("");
+        self.precio_input.set_value("");
+        self.moneda_input.set_value(MONEDA_POR_DEFECTO);
+        self.estado_input.set_value(&Estado::default().to_string());
+        self.fecha_alta_input.set_value("");
+        self.descripcion_input.set_value("");
+
+    }
+
+    fn read_form(&self) -> Result<TipoVivienda, String> {
+        let entero = |input: &Input, campo: &str| {
+            input.value().trim().parse::<i32>().map_err(|_| format!("{} no es un número: '{}'", campo, input.value()))
+        };
+        let mut tipoVivienda = TipoVivienda {
+            identificacion : self.ident_input.value(),
+            calle : self.calle_input.value(),
+            numero : entero(&self.numero_input, "Numero")?,
+            piso: self.piso_input.value(),
+            codigo_postal: self.codigo_postal_input.value(),
+            metros_cuadrados: entero(&self.metros_cuadrados_input, "Metros")?,
+            numero_aseos: entero(&self.numero_aseos_input, "Aseos")?,
+            numero_habitaciones: entero(&self.numero_habitaciones_input, "Habitaciones")?,
+            tipo: Tipo::from_str(&self.tipo_input.value()).map_err(|_| format!("Tipo desconocido: '{}'", self.tipo_input.value()))?,
+            precio: if self.precio_input.value().trim().is_empty() { 0.0 } else { parse_precio(&self.precio_input.value())? },
+            moneda: if self.moneda_input.value().trim().is_empty() { String::from(MONEDA_POR_DEFECTO) } else { self.moneda_input.value().trim().to_uppercase() },
+            estado: Estado::from_str(&self.estado_input.value()).map_err(|_| format!("Estado desconocido: '{}'", self.estado_input.value()))?,
+            // Las altas sin fecha toman la de hoy
+            fecha_alta: match parse_fecha(&self.fecha_alta_input.value())? {
+                fecha if fecha.is_empty() => today(),
+                fecha => fecha,
+            },
+            descripcion: self.descripcion_input.value(),
+            municipio: self.municipio_input.value(),
+            provincia: self.provincia_input.value()
+        };
+        self.codigos_postales.complete(&mut tipoVivienda);
+        match self.codigos_postales.check(&tipoVivienda).into_iter().find(|aviso| aviso.is_error()) {
+            Some(aviso) => Err(aviso.to_string()),
+            None => Ok(tipoVivienda),
+        }
+    }
+
+    // Avisos no bloqueantes del catálogo de códigos postales, para añadir al mensaje de estado
+    fn warnings(&self, tipoVivienda: &TipoVivienda) -> Option<String> {
+        let avisos: Vec<String> = self.codigos_postales.check(tipoVivienda).iter().map(|aviso| aviso.to_string()).collect();
+        if avisos.is_empty() {
+            None
+        } else {
+            Some(avisos.join("; "))
+        }
+    }
+
+    // Pide confirmación si ya hay viviendas con la misma dirección
+    fn confirm_duplicates(&self, tipoVivienda: &TipoVivienda) -> bool {
+        let otras = crate::duplicados::find_matches(tipoVivienda, &self.model);
+        if otras.is_empty() {
+            return true;
+        }
+        let lista: Vec<String> = otras.iter().map(|e| e.toScreen()).collect();
+        let pregunta = format!("Ya existen viviendas con la misma dirección:\n\n{}\n\n¿Crear la vivienda {} de todos modos?",
+            lista.join("\n"), tipoVivienda.identificacion);
+        dialog::choice2_default(&pregunta, "Cancelar", "Crear", "") == Some(1)
+    }
+
+    // Posiciones en model de los elementos seleccionados en list_browser
+    fn selection(&self) -> Vec<usize> {
+        (1..=self.list_browser.size())
+            .filter(|linea| self.list_browser.selected(*linea))
+            .filter_map(|linea| self.visibles.get((linea - 1) as usize).copied())
+            .collect()
+    }
+
+    // Solo hay elemento seleccionado cuando la selección es de exactamente uno
+    fn selected(&self) -> Option<usize> {
+        match self.selection().as_slice() {
+            [index] => Some(*index),
+            _ => None,
+        }
+    }
+
+    fn selection_ids(&self) -> Vec<String> {
+        self.selection().iter().map(|i| self.model[*i].identificacion.clone()).collect()
+    }
+
+    // Las operaciones masivas se aplican en el DAO de una vez y se reflejan en model
+    // sin perder el resto de cambios pendientes
+    fn bulk_update<F>(&mut self, ids: &[String], resultado: Result<usize, Box<dyn std::error::Error>>, operacion: &str, mut cambio: F)
+    where F: FnMut(&mut TipoVivienda) {
+        match resultado {
+            Ok(_) => {
+                for tipoVivienda in self.model.iter_mut().filter(|e| ids.contains(&e.identificacion)) {
+                    cambio(tipoVivienda);
+                }
+                self.set_status(&format!("{}: {} viviendas", operacion, ids.len()), false);
+            }
+            Err(e) => self.set_status(&format!("Error en {}: {}", operacion.to_lowercase(), e), true),
+        }
+        self.clear_edit();
+        self.sender.send(Message::Filter);
+    }
+
+    // Otro programa ha cambiado los datos: sin cambios pendientes basta recargar; con ellos se fusionan
+    // y los conflictos se resuelven en una ventana aparte. Hasta que se acepta la recarga el DAO sigue
+    // con lo leído antes, para que al guardar lo cambiado en disco dé conflicto en lugar de pisarse
+    fn reload_external(&mut self) {
+        let (base, instantanea) = {
+            let mut tipoViviendaDAO = self.tipoViviendaDAO.lock().unwrap();
+            (tipoViviendaDAO.asVector(), tipoViviendaDAO.snapshot())
+        };
+        let instantanea = match instantanea {
+            Ok(instantanea) => instantanea,
+            Err(e) => {
+                self.set_status(&format!("Los datos han cambiado en disco pero no se pudieron leer: {}", e), true);
+                return;
+            }
+        };
+        let remoto = instantanea.datos();
+        let resultado = if !self.dirty {
+            match dialog::choice2_default("Los datos han cambiado en disco. ¿Recargar?", "Ignorar", "Recargar", "") {
+                Some(1) => Some(remoto),
+                _ => None,
+            }
+        } else {
+            let fusion = crate::fusion::merge(&base, &self.model, &remoto);
+            let pregunta = if fusion.conflictos.is_empty() {
+                String::from("Los datos han cambiado en disco. ¿Recargar?\n\nSus cambios sin guardar se conservarán.")
+            } else {
+                format!("Los datos han cambiado en disco. ¿Recargar?\n\n{} viviendas modificadas aquí también han cambiado en disco.", fusion.conflictos.len())
+            };
+            if dialog::choice2_default(&pregunta, "Ignorar", "Recargar", "") != Some(1) {
+                None
+            } else if fusion.conflictos.is_empty() {
+                Some(fusion.resultado)
+            } else {
+                fusion::show(fusion)
+            }
+        };
+        match resultado {
+            Some(resultado) => {
+                self.tipoViviendaDAO.lock().unwrap().accept(instantanea);
+                let mensaje = if self.dirty { "Datos recargados desde disco y fusionados con los cambios sin guardar" } else { "Datos recargados desde disco" };
+                self.model = resultado;
+                self.clear_edit();
+                self.set_status(mensaje, false);
+                self.sender.send(Message::Filter);
+            }
+            None => self.tipoViviendaDAO.lock().unwrap().ignore(&instantanea),
+        }
+    }
+
+    // Otro usuario ha guardado la vivienda después de leerla aquí; false si se cancela el guardado
+    fn resolve_conflict(&mut self, conflicto: &ConflictoVersion) -> bool {
+        let pregunta = format!("{}.\n\nRecargar descarta sus cambios en esta vivienda, Sobrescribir los guarda encima \
+            y Fusionar combina los campos cambiados en cada lado.", conflicto);
+        let opcion = dialog::choice2_default(&pregunta, "Recargar", "Sobrescribir", "Fusionar");
+        let posicion = self.model.iter().position(|e| e.identificacion == conflicto.identificacion);
+        let base = self.tipoViviendaDAO.lock().unwrap().asVector().into_iter().find(|e| e.identificacion == conflicto.identificacion);
+        let resultado = match (opcion, posicion) {
+            (Some(1), Some(i)) => self.tipoViviendaDAO.lock().unwrap().overwrite(&self.model[i]).map(|_| ()),
+            (Some(0), _) | (Some(2), _) => match self.tipoViviendaDAO.lock().unwrap().accept_remote(&conflicto.identificacion) {
+                Ok(remoto) => {
+                    match (opcion, posicion, remoto, base) {
+                        (Some(2), Some(i), Some(remoto), Some(base)) => {
+                            let (fusionada, campos) = crate::fusion::merge_fields(&base, &self.model[i], &remoto);
+                            self.model[i] = fusionada;
+                            if !campos.is_empty() {
+                                dialog::message_default(&format!("Cambiados en los dos lados, se conserva su valor: {}", campos.join(", ")));
+                            }
+                        }
+                        // Si la vivienda ya no existe se guarda la versión local como nueva
+                        (Some(2), _, None, _) => {}
+                        (_, Some(i), Some(remoto), _) => self.model[i] = remoto,
+                        (_, Some(i), None, _) => {
+                            self.model.remove(i);
+                        }
+                        (_, None, Some(remoto), _) => self.model.push(remoto),
+                        _ => {}
+                    }
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            },
+            _ => return false,
+        };
+        match resultado {
+            Ok(()) => true,
+            Err(e) => {
+                self.set_status(&format!("No se pudo resolver el conflicto: {}", e), true);
+                false
+            }
+        }
+    }
 
+    fn set_status(&mut self, mensaje: &str, error: bool) {
+        self.ultimo_mensaje = mensaje.to_string();
+        self.ultimo_error = error;
+        self.update_status();
+    }
+
+    fn update_status(&mut self) {
+        let seleccion = self.selection();
+        let seleccionado = match seleccion.as_slice() {
+            [] => String::from("Seleccionado: -"),
+            [i] => format!("Seleccionado: {}", self.model[*i].identificacion),
+            varios => format!("Seleccionados: {}", varios.len()),
+        };
+        let mut texto = format!("Registros: {}  |  Filtrados: {}  |  {}", self.model.len(), self.visibles.len(), seleccionado);
+        if self.dirty {
+            texto.push_str("  |  Cambios sin guardar");
+        }
+        if !self.ultimo_mensaje.is_empty() {
+            texto.push_str("  |  ");
+            texto.push_str(&self.ultimo_mensaje);
+        }
+        self.status_bar.set_label(&texto);
+        self.status_bar.set_label_color(if self.ultimo_error { Color::Red } else { Color::Foreground });
+        self.status_bar.redraw();
     }
 
     pub fn show(&mut self) {
         self.wind.end();
         self.wind.show();
+        let sender = self.sender;
+        app::add_timeout3(INTERVALO_VIGILANCIA, move |handle| {
+            sender.send(Message::ExternalChange);
+            app::repeat_timeout3(INTERVALO_VIGILANCIA, handle);
+        });
+        // Copias periódicas; el temporizador solo avisa al bucle de mensajes
+        if self.configuracion.copias.intervalo_minutos > 0 {
+            let segundos = self.configuracion.copias.intervalo_minutos as f64 * 60.0;
+            let sender = self.sender;
+            app::add_timeout3(segundos, move |handle| {
+                sender.send(Message::Backup);
+                app::repeat_timeout3(segundos, handle);
+            });
+        }
         while self.app.wait() {
+            // La ventana de gráficos no mantiene abierta la aplicación
+            if !self.wind.shown() {
+                break;
+            }
             match self.receiver.recv() {
+                // Mientras trabaja el hilo de fondo el DAO está ocupado y el modelo no se toca;
+                // los temporizadores lo volverán a intentar en la siguiente vuelta
+                Some(Message::ExternalChange) | Some(Message::Backup) if self.tarea.is_some() => {}
+                Some(mensaje) if self.tarea.is_some() && !matches!(mensaje, Message::Select | Message::Filter | Message::Cancel | Message::Task(_)) => {
+                    self.set_status("Espere a que termine la tarea en curso", true);
+                }
                 Some(Message::Create) => {
-                    self.model.push(TipoVivienda { 
-                        identificacion : self.ident_input.value(),
-                        calle : self.calle_input.value(),
-                        numero : self.numero_input.value().parse().unwrap(),
-                        piso: self.piso_input.value(),
-                        codigo_postal: self.codigo_postal_input.value(),
-                        metros_cuadrados: self.metros_cuadrados_input.value().parse().unwrap(),
-                        numero_aseos: self.numero_aseos_input.value().parse().unwrap(),
-                        numero_habitaciones: self.numero_habitaciones_input.value().parse().unwrap(),
-                        tipo: Tipo::from_str(&self.tipo_input.value()).unwrap()
-                    });
-                    self.clear_edit();
-                    self.sender.send(Message::Filter);
+                    match self.read_form() {
+                        Ok(tipoVivienda) if tipoVivienda.identificacion.trim().is_empty() => {
+                            self.set_status("El Id es obligatorio", true);
+                        }
+                        Ok(tipoVivienda) if self.model.iter().any(|e| e.identificacion == tipoVivienda.identificacion) => {
+                            self.set_status(&format!("Ya existe una vivienda con Id {}", tipoVivienda.identificacion), true);
+                        }
+                        Ok(tipoVivienda) if !self.confirm_duplicates(&tipoVivienda) => {
+                            self.set_status(&format!("No se ha creado la vivienda {}", tipoVivienda.identificacion), false);
+                        }
+                        Ok(tipoVivienda) => {
+                            let avisos = self.warnings(&tipoVivienda);
+                            let mensaje = match &avisos {
+                                Some(avisos) => format!("Creada vivienda {}. Atención: {}", tipoVivienda.identificacion, avisos),
+                                None => format!("Creada vivienda {}", tipoVivienda.identificacion),
+                            };
+                            self.model.push(tipoVivienda);
+                            self.dirty = true;
+                            self.clear_edit();
+                            self.set_status(&mensaje, avisos.is_some());
+                            self.sender.send(Message::Filter);
+                        }
+                        Err(e) => self.set_status(&e, true),
+                    }
                 }
                 Some(Message::Update) => {
-                    if self.list_browser.value() > 0 {
-                        let text_selection = self.list_browser.text(self.list_browser.value()).unwrap();
-                        let search_result = self.model.iter_mut().filter(|e| {
-                            return e.toScreen().eq_ignore_ascii_case(&text_selection)
-                        }).next();
-                        match search_result {
-                            Some(tipoVivienda) => {
-                                tipoVivienda.calle = self.calle_input.value();
-                                tipoVivienda.numero = self.numero_input.value().parse().unwrap();
-                                tipoVivienda.piso = self.piso_input.value();
-                                tipoVivienda.codigo_postal = self.codigo_postal_input.value();
-                                tipoVivienda.metros_cuadrados = self.metros_cuadrados_input.value().parse().unwrap();
-                                tipoVivienda.numero_aseos = self.numero_aseos_input.value().parse().unwrap();
-                                tipoVivienda.numero_habitaciones = self.numero_habitaciones_input.value().parse().unwrap();
-                                tipoVivienda.tipo = Tipo::from_str(&self.tipo_input.value()).unwrap();
-                                self.clear_edit();
-                                self.sender.send(Message::Filter);
-                                self.sender.send(Message::Select);
-                            },
-                            _ => {
-                                println!("ELEMENTO NO ENCONTRADO!!!");
-                            } 
+                    match self.selected() {
+                        Some(index) => {
+                            match self.read_form() {
+                                Ok(mut tipoVivienda) => {
+                                    tipoVivienda.identificacion = self.model[index].identificacion.clone();
+                                    let avisos = self.warnings(&tipoVivienda);
+                                    let mensaje = match &avisos {
+                                        Some(avisos) => format!("Modificada vivienda {}. Atención: {}", tipoVivienda.identificacion, avisos),
+                                        None => format!("Modificada vivienda {}", tipoVivienda.identificacion),
+                                    };
+                                    self.model[index] = tipoVivienda;
+                                    self.dirty = true;
+                                    self.clear_edit();
+                                    self.set_status(&mensaje, avisos.is_some());
+                                    self.sender.send(Message::Filter);
+                                    self.sender.send(Message::Select);
+                                }
+                                Err(e) => self.set_status(&e, true),
+                            }
                         }
-                    } else {
-                        println!("NO HAY ELEMENTO PARA MODIFICAR!!!");
+                        None => self.set_status("No hay elemento para modificar", true),
                     }
                 }
+                Some(Message::Delete) if self.selection().len() > 1 => {
+                    self.sender.send(Message::BulkDelete);
+                }
                 Some(Message::Delete) => {
-                    if self.list_browser.value() > 0 {
-                        let text_selection = self.list_browser.text(self.list_browser.value()).unwrap();
-                        let search_result = self.model.iter().enumerate().filter(|e| {
-                            return e.1.toScreen().eq_ignore_ascii_case(&text_selection)
-                        }).next();
-                        match search_result {
-                            Some((index,persona)) => {
-                                self.model.remove(index);
+                    match self.selected() {
+                        Some(index) => {
+                            let tipoVivienda = &self.model[index];
+                            let pregunta = format!(
+                                "¿Borrar la vivienda {}?\n\n{} {}, {} ({})\n{}, {} m², {} habitaciones, {} aseos\n\nAl guardar se moverá a la papelera.",
+                                tipoVivienda.identificacion, tipoVivienda.calle, tipoVivienda.numero, tipoVivienda.piso, tipoVivienda.codigo_postal,
+                                tipoVivienda.tipo, tipoVivienda.metros_cuadrados, tipoVivienda.numero_habitaciones, tipoVivienda.numero_aseos);
+                            if dialog::choice2_default(&pregunta, "Cancelar", "Borrar", "") == Some(1) {
+                                let tipoVivienda = self.model.remove(index);
+                                self.dirty = true;
                                 self.clear_edit();
+                                self.set_status(&format!("Borrada vivienda {}", tipoVivienda.identificacion), false);
                                 self.sender.send(Message::Filter);
                                 self.sender.send(Message::Select);
-                            },
-                            _ => {
-                                println!("ELEMENTO NO ENCONTRADO!!!");
-                            } 
+                            }
                         }
+                        None => self.set_status("No hay elemento para eliminar", true),
+                    }
+                }
+                Some(Message::BulkDelete) => {
+                    let ids = self.selection_ids();
+                    if ids.is_empty() {
+                        self.set_status("No hay elementos seleccionados", true);
                     } else {
-                        println!("NO HAY ELEMENTO PARA ELIMINAR!!!");
+                        let pregunta = format!("¿Borrar {} viviendas?\n\n{}\n\nSe moverán a la papelera inmediatamente.", ids.len(), ids.join(", "));
+                        if dialog::choice2_default(&pregunta, "Cancelar", "Borrar", "") == Some(1) {
+                            match self.tipoViviendaDAO.lock().unwrap().remove_many(&ids) {
+                                Ok(_) => {
+                                    self.model.retain(|e| !ids.contains(&e.identificacion));
+                                    self.set_status(&format!("Borradas {} viviendas", ids.len()), false);
+                                }
+                                Err(e) => self.set_status(&format!("Error al borrar: {}", e), true),
+                            }
+                            self.clear_edit();
+                            self.sender.send(Message::Filter);
+                        }
+                    }
+                }
+                Some(Message::BulkTipo) => {
+                    let ids = self.selection_ids();
+                    if ids.is_empty() {
+                        self.set_status("No hay elementos seleccionados", true);
+                    } else if let Some(valor) = dialog::input_default(&format!("Nuevo tipo para {} viviendas (Apartamento, Casa, Chalet):", ids.len()), "") {
+                        match Tipo::from_str(valor.trim()) {
+                            Ok(tipo) => {
+                                let resultado = self.tipoViviendaDAO.lock().unwrap().update_tipo_many(&ids, tipo.clone());
+                                self.bulk_update(&ids, resultado, "Cambio de tipo", |e| e.tipo = tipo.clone());
+                            }
+                            Err(_) => self.set_status(&format!("Tipo desconocido: '{}'", valor), true),
+                        }
+                    }
+                }
+                Some(Message::BulkCodigoPostal) => {
+                    let ids = self.selection_ids();
+                    if ids.is_empty() {
+                        self.set_status("No hay elementos seleccionados", true);
+                    } else if let Some(valor) = dialog::input_default(&format!("Nuevo código postal para {} viviendas:", ids.len()), "") {
+                        let codigo_postal = valor.trim().to_string();
+                        if let Err(aviso) = codigos_postales::check_format(&codigo_postal) {
+                            self.set_status(&aviso.to_string(), true);
+                        } else {
+                            let resultado = self.tipoViviendaDAO.lock().unwrap().update_codigo_postal_many(&ids, &codigo_postal);
+                            self.bulk_update(&ids, resultado, "Cambio de código postal", |e| e.codigo_postal = codigo_postal.clone());
+                        }
+                    }
+                }
+                Some(Message::ExportSelection) => {
+                    let seleccion: Vec<TipoVivienda> = self.selection().iter().map(|i| self.model[*i].clone()).collect();
+                    if seleccion.is_empty() {
+                        self.set_status("No hay elementos seleccionados", true);
+                    } else if let Some(path) = self.choose_file(NativeFileChooserType::BrowseSaveFile, "Exportar selección", FILTRO_EXPORTAR) {
+                        self.export(&path, &seleccion);
+                    }
+                }
+                Some(Message::Report(agrupacion)) => {
+                    if let Some(path) = self.choose_file(NativeFileChooserType::BrowseSaveFile, "Catálogo en PDF", FILTRO_PDF) {
+                        let path = path.with_extension("pdf");
+                        let datos: Vec<TipoVivienda> = self.visibles.iter().map(|i| self.model[*i].clone()).collect();
+                        match informe::catalogue(&path, &datos, agrupacion) {
+                            Ok(()) => self.set_status(&format!("Catálogo de {} viviendas generado en {}", datos.len(), path.display()), false),
+                            Err(e) => self.set_status(&format!("Error al generar el catálogo: {}", e), true),
+                        }
+                    }
+                }
+                Some(Message::FactSheet) => {
+                    match self.selected() {
+                        Some(index) => {
+                            let tipoVivienda = self.model[index].clone();
+                            if let Some(path) = self.choose_file(NativeFileChooserType::BrowseSaveFile, "Ficha en PDF", FILTRO_PDF) {
+                                let path = path.with_extension("pdf");
+                                match informe::fact_sheet(&path, &tipoVivienda) {
+                                    Ok(()) => self.set_status(&format!("Ficha de {} generada en {}", tipoVivienda.identificacion, path.display()), false),
+                                    Err(e) => self.set_status(&format!("Error al generar la ficha: {}", e), true),
+                                }
+                            }
+                        }
+                        None => self.set_status("Seleccione una única vivienda para generar su ficha", true),
+                    }
+                }
+                Some(Message::History) => {
+                    match self.selected() {
+                        Some(index) => {
+                            let id = self.model[index].identificacion.clone();
+                            match historial::show(&id, &mut self.tipoViviendaDAO.lock().unwrap()) {
+                                Ok(None) => {}
+                                Ok(Some(tipoVivienda)) => {
+                                    // Ya está guardada; sustituye también la copia de model
+                                    self.model[index] = tipoVivienda;
+                                    self.clear_edit();
+                                    self.set_status(&format!("Restaurada una versión anterior de la vivienda {}", id), false);
+                                    self.sender.send(Message::Filter);
+                                    self.sender.send(Message::Select);
+                                }
+                                Err(e) => self.set_status(&format!("Error al leer el historial de {}: {}", id, e), true),
+                            }
+                        }
+                        None => self.set_status("Seleccione una única vivienda para ver su historial", true),
+                    }
+                }
+                Some(Message::Statistics) => {
+                    // Con cambios sin guardar el modelo no coincide con la base de datos
+                    let estadisticas = if self.dirty {
+                        Ok(Estadisticas::calculate(&self.model))
+                    } else {
+                        self.tipoViviendaDAO.lock().unwrap().statistics()
+                    };
+                    match estadisticas {
+                        Ok(estadisticas) => {
+                            let titulo = if self.dirty { "Estadísticas (cambios sin guardar)" } else { "Estadísticas" };
+                            estadisticas::show(titulo, &estadisticas);
+                        }
+                        Err(e) => self.set_status(&format!("Error al calcular las estadísticas: {}", e), true),
+                    }
+                }
+                Some(Message::Normalize) => {
+                    // Solo cambia el formulario; se aplica al pulsar Crear o Modificar
+                    let direccion = Direccion::from_fields(
+                        &self.calle_input.value(),
+                        self.numero_input.value().trim().parse().ok(),
+                        &self.piso_input.value(),
+                        &self.codigo_postal_input.value(),
+                        &self.municipio_input.value(),
+                        &self.provincia_input.value()).normalize();
+                    self.calle_input.set_value(&direccion.calle());
+                    if let Some(numero) = direccion.numero {
+                        self.numero_input.set_value(&numero.to_string());
+                    }
+                    self.piso_input.set_value(&direccion.piso_puerta());
+                    self.codigo_postal_input.set_value(&direccion.codigo_postal);
+                    self.municipio_input.set_value(&direccion.municipio);
+                    self.provincia_input.set_value(&direccion.provincia);
+                    self.set_status(&format!("Dirección normalizada: {}", direccion), false);
+                }
+                Some(Message::PostalCode) => {
+                    // Solo se consulta con el código completo; rellena lo que esté vacío
+                    let codigo_postal = self.codigo_postal_input.value().trim().to_string();
+                    if codigo_postal.len() == 5 {
+                        if let Err(aviso) = codigos_postales::check_format(&codigo_postal) {
+                            self.set_status(&aviso.to_string(), true);
+                        } else {
+                            let municipios: Vec<String> = self.codigos_postales.find(&codigo_postal).iter().map(|entrada| entrada.municipio.clone()).collect();
+                            if self.municipio_input.value().trim().is_empty() && municipios.len() == 1 {
+                                self.municipio_input.set_value(&municipios[0]);
+                            }
+                            if self.provincia_input.value().trim().is_empty() {
+                                self.provincia_input.set_value(direccion::provincia(&codigo_postal).unwrap_or_default());
+                            }
+                            if municipios.is_empty() {
+                                self.set_status(&codigos_postales::Aviso::Desconocido(codigo_postal).to_string(), true);
+                            } else {
+                                self.set_status(&format!("Código postal {}: {}", codigo_postal, municipios.join(", ")), false);
+                            }
+                        }
+                    }
+                }
+                Some(Message::Charts) => {
+                    // Se crea al pedirla por primera vez, fuera del grupo de la ventana principal
+                    let datos: Vec<TipoVivienda> = self.visibles.iter().map(|i| self.model[*i].clone()).collect();
+                    let graficos = self.graficos.get_or_insert_with(graficos::Graficos::new);
+                    graficos.update(&datos);
+                    graficos.show();
+                }
+                Some(Message::Duplicates) => {
+                    let fusionadas = duplicados::show(&mut self.tipoViviendaDAO.lock().unwrap(), &mut self.model);
+                    if fusionadas > 0 {
+                        self.clear_edit();
+                        self.set_status(&format!("Fusionadas {} viviendas duplicadas", fusionadas), false);
+                        self.sender.send(Message::Filter);
+                    }
+                }
+                Some(Message::Import) => {
+                    if let Some(path) = self.choose_file(NativeFileChooserType::BrowseFile, "Importar viviendas", FILTRO_DATOS) {
+                        self.start_task(Trabajo::Import(path));
+                    }
+                }
+                Some(Message::ImportWizard) => {
+                    if let Some(importadas) = asistente_importacion::show(&self.model) {
+                        let total = importadas.len();
+                        self.model.extend(importadas);
+                        self.dirty = true;
+                        self.set_status(&format!("Importadas {} viviendas", total), false);
+                        self.sender.send(Message::Filter);
+                    }
+                }
+                Some(Message::Export) => {
+                    if let Some(path) = self.choose_file(NativeFileChooserType::BrowseSaveFile, "Exportar viviendas", FILTRO_EXPORTAR) {
+                        let datos = self.model.clone();
+                        self.export(&path, &datos);
+                    }
+                }
+                Some(Message::ExportFiltered) => {
+                    if let Some(path) = self.choose_file(NativeFileChooserType::BrowseSaveFile, "Exportar viviendas filtradas", FILTRO_EXPORTAR) {
+                        let datos: Vec<TipoVivienda> = self.visibles.iter().map(|i| self.model[*i].clone()).collect();
+                        self.export(&path, &datos);
+                    }
+                }
+                Some(Message::Trash) => {
+                    match papelera::show(&mut self.tipoViviendaDAO.lock().unwrap()) {
+                        Ok(restauradas) if restauradas.is_empty() => {}
+                        Ok(restauradas) => {
+                            let mensaje = format!("Restauradas {} viviendas", restauradas.len());
+                            for tipoVivienda in restauradas {
+                                if !self.model.iter().any(|e| e.identificacion == tipoVivienda.identificacion) {
+                                    self.model.push(tipoVivienda);
+                                }
+                            }
+                            // Ya están guardadas en la base de datos y en el CSV
+                            self.set_status(&mensaje, false);
+                            self.sender.send(Message::Filter);
+                        }
+                        Err(e) => self.set_status(&format!("No se pudo abrir la papelera: {}", e), true),
+                    }
+                }
+                Some(Message::ExternalChange) => {
+                    let cambiados = self.tipoViviendaDAO.lock().unwrap().changed_on_disk();
+                    match cambiados {
+                        Ok(true) => self.reload_external(),
+                        Ok(false) => {}
+                        Err(e) => self.set_status(&format!("No se pudo comprobar si los datos han cambiado: {}", e), true),
+                    }
+                }
+                Some(Message::Backup) => {
+                    match self.tipoViviendaDAO.lock().unwrap().backup() {
+                        Ok(path) => self.set_status(&format!("Copia de seguridad en {}", path.display()), false),
+                        Err(e) => self.set_status(&format!("Error en la copia de seguridad: {}", e), true),
+                    }
+                }
+                Some(Message::RestoreBackup) => {
+                    let restaurada = copias::show(&mut self.tipoViviendaDAO.lock().unwrap(), self.dirty);
+                    match restaurada {
+                        Ok(false) => {}
+                        Ok(true) => {
+                            self.model = self.tipoViviendaDAO.lock().unwrap().asVector();
+                            self.dirty = false;
+                            self.clear_edit();
+                            self.set_status("Copia de seguridad restaurada", false);
+                            self.sender.send(Message::Filter);
+                        }
+                        Err(e) => self.set_status(&format!("No se pudieron leer las copias de seguridad: {}", e), true),
                     }
                 }
                 Some(Message::Save) => {
-                    self.tipoViviendaDAO.save_and_refresh(&self.model);
-                    self.model = self.tipoViviendaDAO.asVector();
-                    self.clear_edit();
-                    self.sender.send(Message::Filter);
-                    self.sender.send(Message::Select);
+                    let datos = self.model.clone();
+                    self.start_task(Trabajo::Save(datos));
                 }
-                Some(Message::Select) => {
-                    if self.list_browser.value() == 0 {
-                        self.update_button.deactivate();
-                        self.delete_button.deactivate();
+                Some(Message::Cancel) => {
+                    if let Some(control) = &self.tarea {
+                        control.cancel();
+                        self.set_status("Cancelando...", false);
+                    }
+                }
+                Some(Message::Task(Evento::Progress(hechos, total))) => {
+                    let porcentaje = if total == 0 { 100.0 } else { hechos as f64 * 100.0 / total as f64 };
+                    self.progress_bar.set_value(porcentaje);
+                }
+                Some(Message::Task(Evento::Saved(resultado))) => {
+                    self.finish_task();
+                    self.saved(resultado);
+                }
+                Some(Message::Task(Evento::Conflict(conflicto))) => {
+                    self.finish_task();
+                    // Resuelto el conflicto se vuelve a guardar; si se cancela se conservan los cambios
+                    if self.resolve_conflict(&conflicto) {
+                        let datos = self.model.clone();
+                        self.start_task(Trabajo::Save(datos));
                     } else {
-                        let text_selection = self.list_browser.text(self.list_browser.value()).unwrap();
-                        let search_result = self.model.iter().filter(|e| {
-                            return e.toScreen().eq_ignore_ascii_case(&text_selection)
-                        }).next();
-
-                        match search_result {
-                            Some(tipoVivienda) => {
-                                self.ident_input.set_value(&tipoVivienda.identificacion);
-                                self.calle_input.set_value(&tipoVivienda.calle);
-                                self.numero_input.set_value(&tipoVivienda.numero.to_string());
-                                self.piso_input.set_value(&tipoVivienda.piso);
-                                self.codigo_postal_input.set_value(&tipoVivienda.codigo_postal);
-                                self.metros_cuadrados_input.set_value(&tipoVivienda.metros_cuadrados.to_string());
-                                self.numero_aseos_input.set_value(&tipoVivienda.numero_aseos.to_string());
-                                self.numero_habitaciones_input.set_value(&tipoVivienda.numero_habitaciones.to_string());
-                                self.tipo_input.set_value(&tipoVivienda.tipo.to_string());
-                                self.update_button.activate();
+                        self.set_status(&format!("Guardado cancelado: {}", conflicto), true);
+                        self.clear_edit();
+                        self.sender.send(Message::Filter);
+                        self.sender.send(Message::Select);
+                    }
+                }
+                Some(Message::Task(Evento::Imported(path, resultado))) => {
+                    self.finish_task();
+                    match resultado {
+                        Ok(importadas) => self.add_imported(&path, importadas),
+                        Err(e) => self.set_status(&format!("Error al importar {}: {}", path.display(), e), true),
+                    }
+                }
+                Some(Message::Task(Evento::Exported(path, total, resultado))) => {
+                    self.finish_task();
+                    match resultado {
+                        Ok(()) => self.set_status(&format!("Exportadas {} viviendas a {}", total, path.display()), false),
+                        Err(e) => self.set_status(&format!("Error al exportar: {}", e), true),
+                    }
+                }
+                Some(Message::Task(Evento::Cancelled)) => {
+                    self.finish_task();
+                    self.set_status("Tarea cancelada", false);
+                }
+                Some(Message::New) => {
+                    if let Some(mut path) = self.choose_database(NativeFileChooserType::BrowseSaveFile, "Nueva base de datos") {
+                        if path.extension().is_none() {
+                            path.set_extension("sqlite");
+                        }
+                        self.open_database(&path);
+                    }
+                }
+                Some(Message::Open) => {
+                    if let Some(path) = self.choose_database(NativeFileChooserType::BrowseFile, "Abrir base de datos") {
+                        self.open_database(&path);
+                    }
+                }
+                Some(Message::OpenRecent(i)) => {
+                    if let Some(path) = self.configuracion.recientes.get(i).cloned() {
+                        self.open_database(Path::new(&path));
+                    }
+                }
+                Some(Message::Select) => {
+                    match self.selected() {
+                        Some(index) => {
+                            let tipoVivienda = self.model[index].clone();
+                            self.ident_input.set_value(&tipoVivienda.identificacion);
+                            self.calle_input.set_value(&tipoVivienda.calle);
+                            self.numero_input.set_value(&tipoVivienda.numero.to_string());
+                            self.piso_input.set_value(&tipoVivienda.piso);
+                            self.codigo_postal_input.set_value(&tipoVivienda.codigo_postal);
+                            self.municipio_input.set_value(&tipoVivienda.municipio);
+                            self.provincia_input.set_value(&tipoVivienda.provincia);
+                            self.metros_cuadrados_input.set_value(&tipoVivienda.metros_cuadrados.to_string());
+                            self.numero_aseos_input.set_value(&tipoVivienda.numero_aseos.to_string());
+                            self.numero_habitaciones_input.set_value(&tipoVivienda.numero_habitaciones.to_string());
+                            self.tipo_input.set_value(&tipoVivienda.tipo.to_string());
+                            self.precio_input.set_value(&format!("{:.2}", tipoVivienda.precio));
+                            self.moneda_input.set_value(&tipoVivienda.moneda);
+                            self.estado_input.set_value(&tipoVivienda.estado.to_string());
+                            self.fecha_alta_input.set_value(&tipoVivienda.fecha_alta);
+                            self.descripcion_input.set_value(&tipoVivienda.descripcion);
+                            self.update_button.activate();
+                            self.delete_button.activate();
+                        }
+                        None => {
+                            self.update_button.deactivate();
+                            if self.selection().is_empty() {
+                                self.delete_button.deactivate();
+                            } else {
                                 self.delete_button.activate();
-                            },
-                            _ => {
-                                println!("ELEMENTO NO ENCONTRADO!!!");
-                            } 
-                        }                        
+                            }
+                        }
                     }
+                    self.update_status();
                 }
                 Some(Message::Filter) => {
-                    let prefix = self.filter_input.value().to_lowercase();
-                    let filter_empty = prefix.trim().eq_ignore_ascii_case("");
+                    let texto = self.filter_input.value();
+                    self.visibles = self.search(&texto);
                     self.list_browser.clear();
-                    for (i,p) in self.model.iter().enumerate() {
-                        if (p.identificacion.eq_ignore_ascii_case(prefix.as_str()) && !filter_empty) || (filter_empty)  {
-                            let item = p.toScreen();
-                            self.list_browser.add(&item);    
-                        }
-                    }                                 
+                    for i in &self.visibles {
+                        self.list_browser.add(&self.model[*i].toScreen());
+                    }
+                    if let Some(graficos) = self.graficos.as_mut().filter(|g| g.shown()) {
+                        let datos: Vec<TipoVivienda> = self.visibles.iter().map(|i| self.model[*i].clone()).collect();
+                        graficos.update(&datos);
+                    }
                     self.sender.send(Message::Select);    
                 }
                 None => {},
                 _ => {}
             }
         }
+        self.save_window();
+    }
+
+    fn save_window(&mut self) {
+        self.configuracion.ventana = Some(Ventana {
+            x: self.wind.x(),
+            y: self.wind.y(),
+            w: self.wind.width(),
+            h: self.wind.height(),
+        });
+        if let Err(e) = self.configuracion.save() {
+            eprintln!("No se pudo guardar la configuración: {}", e);
+        }
     }
     
     pub fn refresh(&mut self, data : Vec<TipoVivienda>) {
-        for (i,p) in data.iter().enumerate() {
-            println!("{} {:?} ",i, p);
-        }    
+        self.model = data;
+        self.dirty = false;
+        self.sender.send(Message::Filter);
     }
 
 }
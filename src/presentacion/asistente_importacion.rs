@@ -0,0 +1,299 @@
+use std::{fs, path::PathBuf};
+
+use fltk::{
+    app, browser::Browser, button::{Button, CheckButton}, dialog::{NativeFileChooser, NativeFileChooserType},
+    enums::{Align, Shortcut}, frame::Frame, group::Flex, menu::{Choice, MenuFlag}, output::Output, prelude::*, window::Window,
+};
+
+use super::{Acciones, menu_label, WIDGET_HEIGHT, WIDGET_PADDING, WIDGET_WIDTH};
+use crate::entidad::TipoVivienda;
+use crate::importacion::{self, Campo, Codificacion, InformeImportacion, OpcionesImportacion};
+
+const FILAS_VISTA_PREVIA: usize = 20;
+const ANCHOS_COLUMNA: [i32; 16] = [WIDGET_WIDTH + WIDGET_PADDING * 3; 16];
+const DELIMITADORES: [(u8, &str); 4] = [(b',', "Coma (,)"), (b';', "Punto y coma (;)"), (b'\t', "Tabulador"), (b'|', "Barra (|)")];
+
+#[derive(Clone, Copy)]
+enum Accion {
+    Browse,
+    Reload,
+    Mapping,
+    Analyze,
+    Import,
+    Cancel,
+}
+
+struct Asistente {
+    path : Option<PathBuf>,
+    filas : Vec<Vec<String>>,
+    informe : Option<InformeImportacion>,
+    path_output : Output,
+    delimitador_choice : Choice,
+    codificacion_choice : Choice,
+    cabecera_check : CheckButton,
+    vista_previa : Browser,
+    mapeo_choices : Vec<Choice>,
+    informe_browser : Browser,
+    import_button : Button,
+}
+
+// Texto literal en una celda de Browser: "@." desactiva los códigos de formato
+fn celda(texto: &str) -> String {
+    format!("@.{}", texto)
+}
+
+impl Asistente {
+
+    fn options(&self) -> OpcionesImportacion {
+        let mut mapeo = std::collections::HashMap::new();
+        for (campo, choice) in Campo::TODOS.iter().zip(self.mapeo_choices.iter()) {
+            // El elemento 0 es "(sin asignar)"
+            if choice.value() > 0 {
+                mapeo.insert(*campo, (choice.value() - 1) as usize);
+            }
+        }
+        OpcionesImportacion {
+            delimitador: DELIMITADORES[self.delimitador_choice.value().max(0) as usize].0,
+            codificacion: if self.codificacion_choice.value() == 1 { Codificacion::Latin1 } else { Codificacion::Utf8 },
+            cabecera: self.cabecera_check.is_checked(),
+            mapeo,
+        }
+    }
+
+    fn browse(&mut self) {
+        let mut dialogo = NativeFileChooser::new(NativeFileChooserType::BrowseFile);
+        dialogo.set_title("Importar CSV");
+        dialogo.set_filter("CSV\t*.{csv,txt}");
+        dialogo.show();
+        let path = dialogo.filename();
+        if path.as_os_str().is_empty() {
+            return;
+        }
+        // Se proponen separador y codificación a partir del propio fichero
+        if let Ok(bytes) = fs::read(&path) {
+            let (texto, codificacion) = match importacion::decode(&bytes, Codificacion::Utf8) {
+                Ok(texto) => (texto, 0),
+                Err(_) => (importacion::decode(&bytes, Codificacion::Latin1).unwrap_or_default(), 1),
+            };
+            let delimitador = importacion::guess_delimiter(&texto);
+            let posicion = DELIMITADORES.iter().position(|(d, _)| *d == delimitador).unwrap_or(0);
+            self.delimitador_choice.set_value(posicion as i32);
+            self.codificacion_choice.set_value(codificacion);
+        }
+        self.path_output.set_value(&path.to_string_lossy());
+        self.path = Some(path);
+        self.reload();
+    }
+
+    fn reload(&mut self) {
+        self.informe = None;
+        self.import_button.deactivate();
+        self.informe_browser.clear();
+        self.vista_previa.clear();
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        let opciones = self.options();
+        self.filas = match importacion::read(&path, &opciones) {
+            Ok(filas) => filas,
+            Err(e) => {
+                self.filas = Vec::new();
+                self.informe_browser.add(&format!("@C1{}", celda(&e.to_string())));
+                return;
+            }
+        };
+
+        for (i, fila) in self.filas.iter().take(FILAS_VISTA_PREVIA).enumerate() {
+            let formato = if i == 0 && opciones.cabecera { "@b" } else { "" };
+            let linea: Vec<String> = fila.iter().map(|texto| format!("{}{}", formato, celda(texto))).collect();
+            self.vista_previa.add(&linea.join("\t"));
+        }
+
+        let columnas = self.filas.iter().map(|fila| fila.len()).max().unwrap_or(0);
+        let cabeceras: Vec<String> = (0..columnas)
+            .map(|c| match self.filas.first() {
+                Some(primera) if opciones.cabecera => format!("{}: {}", c + 1, primera.get(c).cloned().unwrap_or_default()),
+                _ => format!("Columna {}", c + 1),
+            })
+            .collect();
+        let sugerido = if opciones.cabecera {
+            importacion::suggest_mapping(self.filas.first().map(|f| f.as_slice()).unwrap_or(&[]))
+        } else {
+            std::collections::HashMap::new()
+        };
+        for (campo, choice) in Campo::TODOS.iter().zip(self.mapeo_choices.iter_mut()) {
+            choice.clear();
+            choice.add("(sin asignar)", Shortcut::None, MenuFlag::Normal, |_| {});
+            for cabecera in &cabeceras {
+                choice.add(&menu_label(cabecera), Shortcut::None, MenuFlag::Normal, |_| {});
+            }
+            choice.set_value(sugerido.get(campo).map(|c| *c as i32 + 1).unwrap_or(0));
+        }
+    }
+
+    fn analyze(&mut self, existentes: &[TipoVivienda]) {
+        self.informe_browser.clear();
+        self.import_button.deactivate();
+        match importacion::analyze(&self.filas, &self.options(), existentes) {
+            Ok(informe) => {
+                self.informe_browser.add(&format!("@b{}", celda(&format!(
+                    "Aceptadas: {}   Rechazadas: {}   Duplicadas: {}",
+                    informe.aceptados.len(), informe.rechazados.len(), informe.duplicados.len()))));
+                for (fila, motivo) in &informe.rechazados {
+                    self.informe_browser.add(&format!("@C1{}", celda(&format!("Fila {}: rechazada, {}", fila, motivo))));
+                }
+                for (fila, id) in &informe.duplicados {
+                    self.informe_browser.add(&format!("@C4{}", celda(&format!("Fila {}: duplicada, ya existe el Id {}", fila, id))));
+                }
+                for (fila, id) in &informe.misma_direccion {
+                    self.informe_browser.add(&format!("@C4{}", celda(&format!("Fila {}: posible duplicado, misma dirección que el Id {}", fila, id))));
+                }
+                for (fila, tipoVivienda) in &informe.aceptados {
+                    self.informe_browser.add(&celda(&format!("Fila {}: aceptada, Id {}", fila, tipoVivienda.identificacion)));
+                }
+                if !informe.aceptados.is_empty() {
+                    self.import_button.activate();
+                }
+                self.informe = Some(informe);
+            }
+            Err(e) => {
+                self.informe_browser.add(&format!("@C1{}", celda(&e.to_string())));
+            }
+        }
+    }
+}
+
+fn label(texto: &str) -> Frame {
+    Frame::default().with_label(texto).with_align(Align::Right | Align::Inside)
+}
+
+// Ventana modal; devuelve las viviendas aceptadas si el usuario confirma la importación
+pub fn show(existentes: &[TipoVivienda]) -> Option<Vec<TipoVivienda>> {
+    let acciones = Acciones::new();
+    let mut wind = Window::default().with_size(WIDGET_WIDTH * 13, WIDGET_HEIGHT * 30).with_label("Importar CSV");
+
+    let mut principal = Flex::default_fill().column();
+    principal.set_margin(WIDGET_PADDING);
+    principal.set_pad(WIDGET_PADDING / 2);
+
+    let mut fila_fichero = Flex::default().row();
+    let etiqueta = label("Fichero:");
+    fila_fichero.set_size(&etiqueta, WIDGET_WIDTH);
+    let path_output = Output::default();
+    let mut browse_button = Button::default().with_label("Examinar...");
+    fila_fichero.set_size(&browse_button, WIDGET_WIDTH + WIDGET_PADDING * 2);
+    fila_fichero.end();
+    principal.set_size(&fila_fichero, WIDGET_HEIGHT);
+
+    let mut fila_opciones = Flex::default().row();
+    let etiqueta = label("Separador:");
+    fila_opciones.set_size(&etiqueta, WIDGET_WIDTH);
+    let mut delimitador_choice = Choice::default();
+    for (_, nombre) in DELIMITADORES {
+        delimitador_choice.add(nombre, Shortcut::None, MenuFlag::Normal, |_| {});
+    }
+    delimitador_choice.set_value(0);
+    let etiqueta = label("Codificación:");
+    fila_opciones.set_size(&etiqueta, WIDGET_WIDTH + WIDGET_PADDING * 2);
+    let mut codificacion_choice = Choice::default();
+    codificacion_choice.add("UTF-8", Shortcut::None, MenuFlag::Normal, |_| {});
+    codificacion_choice.add("Latin-1 (Windows-1252)", Shortcut::None, MenuFlag::Normal, |_| {});
+    codificacion_choice.set_value(0);
+    let mut cabecera_check = CheckButton::default().with_label("Primera fila es cabecera");
+    cabecera_check.set_checked(true);
+    fila_opciones.set_size(&cabecera_check, WIDGET_WIDTH * 3);
+    fila_opciones.end();
+    principal.set_size(&fila_opciones, WIDGET_HEIGHT);
+
+    let titulo = Frame::default().with_label("Vista previa").with_align(Align::Left | Align::Inside);
+    principal.set_size(&titulo, WIDGET_HEIGHT);
+    let mut vista_previa = Browser::default();
+    vista_previa.set_column_widths(&ANCHOS_COLUMNA);
+
+    let titulo = Frame::default().with_label("Columnas").with_align(Align::Left | Align::Inside);
+    principal.set_size(&titulo, WIDGET_HEIGHT);
+    let mut mapeo_choices = Vec::new();
+    // Dos campos por fila para que la ventana siga cabiendo en pantalla
+    for pareja in Campo::TODOS.chunks(2) {
+        let mut fila = Flex::default().row();
+        for campo in pareja {
+            let marca = if campo.obligatorio() { " *" } else { "" };
+            let etiqueta = label(&format!("{}{}:", campo.nombre(), marca));
+            fila.set_size(&etiqueta, WIDGET_WIDTH * 2);
+            let mut choice = Choice::default();
+            fila.set_size(&choice, WIDGET_WIDTH * 4);
+            acciones.emit(&mut choice, Accion::Mapping);
+            mapeo_choices.push(choice);
+        }
+        Frame::default();
+        fila.end();
+        principal.set_size(&fila, WIDGET_HEIGHT);
+    }
+
+    let titulo = Frame::default().with_label("Informe").with_align(Align::Left | Align::Inside);
+    principal.set_size(&titulo, WIDGET_HEIGHT);
+    let informe_browser = Browser::default();
+
+    let mut fila_botones = Flex::default().row();
+    Frame::default();
+    let mut analyze_button = Button::default().with_label("Analizar");
+    fila_botones.set_size(&analyze_button, WIDGET_WIDTH + WIDGET_PADDING * 2);
+    let mut import_button = Button::default().with_label("Importar");
+    fila_botones.set_size(&import_button, WIDGET_WIDTH + WIDGET_PADDING * 2);
+    let mut cancel_button = Button::default().with_label("Cancelar");
+    fila_botones.set_size(&cancel_button, WIDGET_WIDTH + WIDGET_PADDING * 2);
+    fila_botones.end();
+    principal.set_size(&fila_botones, WIDGET_HEIGHT);
+
+    principal.end();
+    wind.end();
+    wind.resizable(&principal);
+    wind.make_modal(true);
+
+    acciones.emit(&mut browse_button, Accion::Browse);
+    acciones.emit(&mut delimitador_choice, Accion::Reload);
+    acciones.emit(&mut codificacion_choice, Accion::Reload);
+    acciones.emit(&mut cabecera_check, Accion::Reload);
+    acciones.emit(&mut analyze_button, Accion::Analyze);
+    acciones.emit(&mut import_button, Accion::Import);
+    acciones.emit(&mut cancel_button, Accion::Cancel);
+    import_button.deactivate();
+
+    let mut asistente = Asistente {
+        path : None,
+        filas : Vec::new(),
+        informe : None,
+        path_output,
+        delimitador_choice,
+        codificacion_choice,
+        cabecera_check,
+        vista_previa,
+        mapeo_choices,
+        informe_browser,
+        import_button,
+    };
+
+    wind.show();
+    while wind.shown() {
+        app::wait();
+        match acciones.recv() {
+            Some(Accion::Browse) => asistente.browse(),
+            Some(Accion::Reload) => asistente.reload(),
+            Some(Accion::Mapping) => {
+                asistente.informe = None;
+                asistente.import_button.deactivate();
+            }
+            Some(Accion::Analyze) => asistente.analyze(existentes),
+            Some(Accion::Import) => {
+                if let Some(informe) = asistente.informe.take() {
+                    wind.hide();
+                    return Some(informe.aceptados.into_iter().map(|(_, tipoVivienda)| tipoVivienda).collect());
+                }
+            }
+            Some(Accion::Cancel) => wind.hide(),
+            None => {}
+        }
+    }
+    None
+}
@@ -0,0 +1,91 @@
+use fltk::{app, browser::HoldBrowser, button::Button, dialog, frame::Frame, group::Flex, prelude::*, window::Window};
+
+use super::{Acciones, WIDGET_HEIGHT, WIDGET_PADDING, WIDGET_WIDTH};
+use crate::copias::Copia;
+use crate::entidad::TipoViviendaDAO;
+
+#[derive(Clone, Copy)]
+enum Accion {
+    Select,
+    Restore,
+}
+
+fn fill(list_browser: &mut HoldBrowser, lista: &[Copia]) {
+    list_browser.clear();
+    for copia in lista {
+        let registros = match copia.registros {
+            Some(registros) => format!("{} viviendas", registros),
+            None => String::from("no se puede leer"),
+        };
+        let nombre = copia.path.file_name().map(|nombre| nombre.to_string_lossy().to_string()).unwrap_or_default();
+        list_browser.add(&format!("@.{}  |  {}  |  {}", copia.fecha, registros, nombre));
+    }
+}
+
+// Ventana modal; devuelve true si se ha restaurado una copia y hay que volver a leer las viviendas
+pub fn show(tipoViviendaDAO: &mut TipoViviendaDAO, cambios_sin_guardar: bool) -> Result<bool, String> {
+    let lista = tipoViviendaDAO.backups().map_err(|e| e.to_string())?;
+
+    let acciones = Acciones::new();
+    let mut wind = Window::default().with_size(WIDGET_WIDTH * 10, WIDGET_HEIGHT * 14).with_label("Restaurar copia de seguridad");
+
+    let mut principal = Flex::default_fill().column();
+    principal.set_margin(WIDGET_PADDING);
+    principal.set_pad(WIDGET_PADDING);
+
+    let mut list_browser = HoldBrowser::default();
+
+    let mut fila_botones = Flex::default().row();
+    Frame::default();
+    let mut restore_button = Button::default().with_label("Restaurar");
+    fila_botones.set_size(&restore_button, WIDGET_WIDTH + WIDGET_PADDING * 2);
+    fila_botones.end();
+    principal.set_size(&fila_botones, WIDGET_HEIGHT);
+
+    principal.end();
+    wind.end();
+    wind.resizable(&principal);
+    wind.make_modal(true);
+
+    acciones.emit(&mut list_browser, Accion::Select);
+    acciones.emit(&mut restore_button, Accion::Restore);
+    restore_button.deactivate();
+
+    fill(&mut list_browser, &lista);
+    if lista.is_empty() {
+        list_browser.add("No hay copias de seguridad");
+    }
+    wind.show();
+
+    while wind.shown() {
+        app::wait();
+        let seleccion = list_browser.value();
+        match acciones.recv() {
+            Some(Accion::Select) => {
+                if seleccion > 0 && (seleccion as usize) <= lista.len() && lista[(seleccion - 1) as usize].registros.is_some() {
+                    restore_button.activate();
+                } else {
+                    restore_button.deactivate();
+                }
+            }
+            Some(Accion::Restore) if seleccion > 0 => {
+                let copia = &lista[(seleccion - 1) as usize];
+                let mut pregunta = format!("¿Sustituir la base de datos por la copia del {}?\n\nAntes se hará una copia del estado actual.", copia.fecha);
+                if cambios_sin_guardar {
+                    pregunta.push_str("\nLos cambios sin guardar se perderán.");
+                }
+                if dialog::choice2_default(&pregunta, "Cancelar", "Restaurar", "") == Some(1) {
+                    match tipoViviendaDAO.restore_backup(&copia.path) {
+                        Ok(()) => {
+                            wind.hide();
+                            return Ok(true);
+                        }
+                        Err(e) => dialog::alert_default(&format!("No se pudo restaurar la copia: {}", e)),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(false)
+}
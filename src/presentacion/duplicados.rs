@@ -0,0 +1,108 @@
+use fltk::{app, browser::HoldBrowser, button::Button, dialog, frame::Frame, group::Flex, prelude::*, window::Window};
+
+use super::{Acciones, WIDGET_HEIGHT, WIDGET_PADDING, WIDGET_WIDTH};
+use crate::direccion::Direccion;
+use crate::duplicados;
+use crate::entidad::{ScreenOutput, TipoVivienda, TipoViviendaDAO};
+
+#[derive(Clone, Copy)]
+enum Accion {
+    Select,
+    Merge,
+}
+
+// Cada línea del browser: None para la cabecera del grupo, Some((grupo, posición en model)) para una vivienda
+fn fill(list_browser: &mut HoldBrowser, model: &[TipoVivienda], grupos: &[Vec<usize>]) -> Vec<Option<(usize, usize)>> {
+    list_browser.clear();
+    let mut lineas = Vec::new();
+    for (g, grupo) in grupos.iter().enumerate() {
+        list_browser.add(&format!("@b@.{} ({} viviendas)", Direccion::from_tipo_vivienda(&model[grupo[0]]).normalize(), grupo.len()));
+        lineas.push(None);
+        for i in grupo {
+            list_browser.add(&format!("@.    {}", model[*i].toScreen()));
+            lineas.push(Some((g, *i)));
+        }
+    }
+    if grupos.is_empty() {
+        list_browser.add("No hay viviendas con la misma dirección");
+        lineas.push(None);
+    }
+    lineas
+}
+
+// Ventana modal con los grupos de posibles duplicados; la fusión se guarda inmediatamente
+// y se refleja en model. Devuelve el número de viviendas enviadas a la papelera.
+pub fn show(tipoViviendaDAO: &mut TipoViviendaDAO, model: &mut Vec<TipoVivienda>) -> usize {
+    let mut fusionadas = 0;
+    let mut grupos = duplicados::groups(model);
+
+    let acciones = Acciones::new();
+    let mut wind = Window::default().with_size(WIDGET_WIDTH * 11, WIDGET_HEIGHT * 18).with_label("Posibles duplicados");
+
+    let mut principal = Flex::default_fill().column();
+    principal.set_margin(WIDGET_PADDING);
+    principal.set_pad(WIDGET_PADDING);
+
+    let mut list_browser = HoldBrowser::default();
+
+    let mut fila_botones = Flex::default().row();
+    Frame::default().with_label("Seleccione la vivienda que se conserva");
+    let mut merge_button = Button::default().with_label("Fusionar");
+    fila_botones.set_size(&merge_button, WIDGET_WIDTH + WIDGET_PADDING * 2);
+    fila_botones.end();
+    principal.set_size(&fila_botones, WIDGET_HEIGHT);
+
+    principal.end();
+    wind.end();
+    wind.resizable(&principal);
+    wind.make_modal(true);
+
+    acciones.emit(&mut list_browser, Accion::Select);
+    acciones.emit(&mut merge_button, Accion::Merge);
+    merge_button.deactivate();
+
+    let mut lineas = fill(&mut list_browser, model, &grupos);
+    wind.show();
+
+    while wind.shown() {
+        app::wait();
+        let seleccion = match list_browser.value() {
+            0 => None,
+            linea => lineas.get((linea - 1) as usize).copied().flatten(),
+        };
+        match acciones.recv() {
+            Some(Accion::Select) => {
+                if seleccion.is_some() {
+                    merge_button.activate();
+                } else {
+                    merge_button.deactivate();
+                }
+            }
+            Some(Accion::Merge) => {
+                if let Some((g, conservar)) = seleccion {
+                    let otras: Vec<TipoVivienda> = grupos[g].iter().filter(|i| **i != conservar).map(|i| model[*i].clone()).collect();
+                    let ids: Vec<String> = otras.iter().map(|e| e.identificacion.clone()).collect();
+                    let pregunta = format!(
+                        "Se conservará la vivienda {} completando los datos que le falten.\n\nSe moverán a la papelera: {}",
+                        model[conservar].identificacion, ids.join(", "));
+                    if dialog::choice2_default(&pregunta, "Cancelar", "Fusionar", "") == Some(1) {
+                        let fusionada = duplicados::merge(&model[conservar], &otras);
+                        match tipoViviendaDAO.merge(&fusionada, &ids) {
+                            Ok(_) => {
+                                model[conservar] = fusionada;
+                                model.retain(|e| !ids.contains(&e.identificacion));
+                                fusionadas += ids.len();
+                                grupos = duplicados::groups(model);
+                                lineas = fill(&mut list_browser, model, &grupos);
+                            }
+                            Err(e) => dialog::alert_default(&format!("No se pudo fusionar: {}", e)),
+                        }
+                        acciones.send(Accion::Select);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    fusionadas
+}
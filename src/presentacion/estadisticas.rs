@@ -0,0 +1,43 @@
+use fltk::{app, browser::Browser, button::Button, frame::Frame, group::Flex, prelude::*, window::Window};
+
+use super::{WIDGET_HEIGHT, WIDGET_PADDING, WIDGET_WIDTH};
+use crate::estadisticas::Estadisticas;
+
+// Ventana modal de solo lectura con el resumen del catálogo
+pub fn show(titulo: &str, estadisticas: &Estadisticas) {
+    let mut wind = Window::default().with_size(WIDGET_WIDTH * 11, WIDGET_HEIGHT * 18).with_label(titulo);
+
+    let mut principal = Flex::default_fill().column();
+    principal.set_margin(WIDGET_PADDING);
+    principal.set_pad(WIDGET_PADDING);
+
+    let mut browser = Browser::default();
+    for linea in estadisticas.lines() {
+        // Los títulos de sección en negrita; "@." evita que se interpreten otros caracteres de formato
+        if linea.ends_with(':') {
+            browser.add(&format!("@b@.{}", linea));
+        } else {
+            browser.add(&format!("@.{}", linea));
+        }
+    }
+
+    let mut fila_botones = Flex::default().row();
+    Frame::default();
+    let mut close_button = Button::default().with_label("Cerrar");
+    fila_botones.set_size(&close_button, WIDGET_WIDTH + WIDGET_PADDING * 2);
+    fila_botones.end();
+    principal.set_size(&fila_botones, WIDGET_HEIGHT);
+
+    principal.end();
+    wind.end();
+    wind.resizable(&principal);
+    wind.make_modal(true);
+
+    let mut ventana = wind.clone();
+    close_button.set_callback(move |_| ventana.hide());
+
+    wind.show();
+    while wind.shown() {
+        app::wait();
+    }
+}
@@ -0,0 +1,130 @@
+use fltk::{app, browser::{Browser, HoldBrowser}, button::Button, frame::Frame, group::Flex, prelude::*, window::Window};
+
+use super::{Acciones, WIDGET_HEIGHT, WIDGET_PADDING, WIDGET_WIDTH};
+use crate::entidad::{ScreenOutput, TipoVivienda};
+use crate::fusion::{self, Fusion};
+use crate::versiones;
+
+#[derive(Clone, Copy)]
+enum Accion {
+    Select,
+    Local,
+    Remote,
+    Apply,
+}
+
+fn fill(list_browser: &mut HoldBrowser, fusion: &Fusion, remotas: &[bool]) {
+    let seleccion = list_browser.value();
+    list_browser.clear();
+    for (conflicto, remota) in fusion.conflictos.iter().zip(remotas) {
+        let elegida = if *remota { "disco" } else { "mía" };
+        list_browser.add(&format!("@.{}  [{}]", conflicto.identificacion, elegida));
+    }
+    list_browser.select(seleccion);
+}
+
+fn fill_changes(cambios_browser: &mut Browser, local: &Option<TipoVivienda>, remoto: &Option<TipoVivienda>) {
+    cambios_browser.clear();
+    let describe = |tipoVivienda: &Option<TipoVivienda>| match tipoVivienda {
+        Some(tipoVivienda) => tipoVivienda.toScreen(),
+        None => String::from("(borrada)"),
+    };
+    cambios_browser.add(&format!("@b@.Mía: {}", describe(local)));
+    cambios_browser.add(&format!("@b@.Disco: {}", describe(remoto)));
+    let value = |tipoVivienda: &Option<TipoVivienda>| tipoVivienda.as_ref().and_then(|e| serde_json::to_value(e).ok());
+    for (campo, mio, disco) in versiones::diff(value(local), value(remoto)) {
+        cambios_browser.add(&format!("@.{}: '{}' (mía) / '{}' (disco)", campo, mio, disco));
+    }
+}
+
+// Ventana modal para resolver los conflictos uno a uno; None si se cancela
+pub fn show(fusion: Fusion) -> Option<Vec<TipoVivienda>> {
+    let mut remotas = vec![false; fusion.conflictos.len()];
+    let mut aplicar = false;
+
+    let acciones = Acciones::new();
+    let mut wind = Window::default().with_size(WIDGET_WIDTH * 12, WIDGET_HEIGHT * 16).with_label("Conflictos con los datos en disco");
+
+    let mut principal = Flex::default_fill().column();
+    principal.set_margin(WIDGET_PADDING);
+    principal.set_pad(WIDGET_PADDING);
+
+    let aviso = Frame::default().with_label("Estas viviendas han cambiado aquí y en disco. Elija qué versión conservar.");
+    principal.set_size(&aviso, WIDGET_HEIGHT);
+
+    let mut paneles = Flex::default().row();
+    let mut list_browser = HoldBrowser::default();
+    let mut cambios_browser = Browser::default();
+    paneles.set_size(&list_browser, WIDGET_WIDTH * 3);
+    paneles.end();
+
+    let mut fila_botones = Flex::default().row();
+    let mut local_button = Button::default().with_label("Usar la mía");
+    fila_botones.set_size(&local_button, WIDGET_WIDTH * 2);
+    let mut remote_button = Button::default().with_label("Usar la del disco");
+    fila_botones.set_size(&remote_button, WIDGET_WIDTH * 2);
+    Frame::default();
+    let mut apply_button = Button::default().with_label("Aplicar");
+    fila_botones.set_size(&apply_button, WIDGET_WIDTH + WIDGET_PADDING * 2);
+    let mut cancel_button = Button::default().with_label("Cancelar");
+    fila_botones.set_size(&cancel_button, WIDGET_WIDTH + WIDGET_PADDING * 2);
+    fila_botones.end();
+    principal.set_size(&fila_botones, WIDGET_HEIGHT);
+
+    principal.end();
+    wind.end();
+    wind.resizable(&principal);
+    wind.make_modal(true);
+
+    acciones.emit(&mut list_browser, Accion::Select);
+    acciones.emit(&mut local_button, Accion::Local);
+    acciones.emit(&mut remote_button, Accion::Remote);
+    acciones.emit(&mut apply_button, Accion::Apply);
+    let mut ventana = wind.clone();
+    cancel_button.set_callback(move |_| ventana.hide());
+
+    fill(&mut list_browser, &fusion, &remotas);
+    if !fusion.conflictos.is_empty() {
+        list_browser.select(1);
+        acciones.send(Accion::Select);
+    }
+    wind.show();
+
+    while wind.shown() {
+        app::wait();
+        let seleccion = match list_browser.value() {
+            0 => None,
+            linea => Some((linea - 1) as usize),
+        };
+        match (acciones.recv(), seleccion) {
+            (Some(Accion::Select), Some(i)) => {
+                let conflicto = &fusion.conflictos[i];
+                fill_changes(&mut cambios_browser, &conflicto.local, &conflicto.remoto);
+            }
+            (Some(Accion::Local), Some(i)) => {
+                remotas[i] = false;
+                fill(&mut list_browser, &fusion, &remotas);
+            }
+            (Some(Accion::Remote), Some(i)) => {
+                remotas[i] = true;
+                fill(&mut list_browser, &fusion, &remotas);
+            }
+            (Some(Accion::Apply), _) => {
+                aplicar = true;
+                wind.hide();
+            }
+            _ => {}
+        }
+    }
+
+    if !aplicar {
+        return None;
+    }
+    let mut resultado = fusion.resultado.clone();
+    for (conflicto, remota) in fusion.conflictos.iter().zip(&remotas) {
+        if *remota {
+            fusion::take_remote(&mut resultado, conflicto);
+        }
+    }
+    Some(resultado)
+}
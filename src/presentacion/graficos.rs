@@ -0,0 +1,104 @@
+use std::{cell::RefCell, rc::Rc};
+
+use fltk::{draw, enums::{Align, Color, Font, FrameType}, frame::Frame, group::Flex, prelude::*, window::Window};
+
+use super::{WIDGET_HEIGHT, WIDGET_PADDING, WIDGET_WIDTH};
+use crate::entidad::TipoVivienda;
+use crate::estadisticas::{self, Estadisticas};
+
+const INTERVALOS_HISTOGRAMA: usize = 10;
+// Más barras no caben legibles; el resto de códigos postales se agrupa en "Otros"
+const MAXIMO_BARRAS: usize = 12;
+
+type Serie = Rc<RefCell<Vec<(String, usize)>>>;
+
+// Ventana no modal que se actualiza con cada cambio del filtro
+pub struct Graficos {
+    wind: Window,
+    por_tipo: Serie,
+    por_codigo_postal: Serie,
+    histograma: Serie,
+}
+
+fn chart(titulo: &'static str, color: Color) -> (Frame, Serie) {
+    let serie: Serie = Rc::new(RefCell::new(Vec::new()));
+    let mut frame = Frame::default();
+    frame.set_frame(FrameType::DownBox);
+    frame.set_color(Color::White);
+    let datos = serie.clone();
+    frame.draw(move |f| draw_bars(f, titulo, &datos.borrow(), color));
+    (frame, serie)
+}
+
+fn draw_bars(f: &Frame, titulo: &str, barras: &[(String, usize)], color: Color) {
+    let (x, y, w, h) = (f.x() + WIDGET_PADDING, f.y() + WIDGET_PADDING, f.w() - WIDGET_PADDING * 2, f.h() - WIDGET_PADDING * 2);
+    draw::set_draw_color(Color::Black);
+    draw::set_font(Font::HelveticaBold, 14);
+    draw::draw_text2(titulo, x, y, w, 20, Align::Center);
+
+    draw::set_font(Font::Helvetica, 11);
+    let maximo = barras.iter().map(|(_, total)| *total).max().unwrap_or(0);
+    if maximo == 0 {
+        draw::draw_text2("Sin datos", x, y, w, h, Align::Center);
+        return;
+    }
+    // Espacio para el título arriba y las etiquetas abajo
+    let (arriba, abajo) = (y + 20 + 15, y + h - 20);
+    let alto = abajo - arriba;
+    let ancho = w / barras.len() as i32;
+    draw::draw_line(x, abajo, x + w, abajo);
+    for (i, (etiqueta, total)) in barras.iter().enumerate() {
+        let bx = x + ancho * i as i32;
+        let bh = (alto as f64 * *total as f64 / maximo as f64) as i32;
+        draw::draw_rect_fill(bx + 2, abajo - bh, ancho - 4, bh, color);
+        draw::set_draw_color(Color::Black);
+        draw::draw_text2(&total.to_string(), bx, abajo - bh - 15, ancho, 15, Align::Center);
+        draw::draw_text2(etiqueta, bx, abajo, ancho, 20, Align::Center | Align::Clip);
+    }
+}
+
+fn series(grupos: &std::collections::BTreeMap<String, estadisticas::Grupo>) -> Vec<(String, usize)> {
+    let mut barras: Vec<(String, usize)> = grupos.iter().map(|(clave, grupo)| (clave.clone(), grupo.total)).collect();
+    if barras.len() > MAXIMO_BARRAS {
+        barras.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let otros: usize = barras.drain(MAXIMO_BARRAS - 1..).map(|(_, total)| total).sum();
+        barras.push((String::from("Otros"), otros));
+    }
+    barras
+}
+
+impl Graficos {
+    pub fn new() -> Graficos {
+        let mut wind = Window::default().with_size(WIDGET_WIDTH * 10, WIDGET_HEIGHT * 28).with_label("Gráficos");
+
+        let mut principal = Flex::default_fill().column();
+        principal.set_margin(WIDGET_PADDING);
+        principal.set_pad(WIDGET_PADDING);
+        let (_, por_tipo) = chart("Viviendas por tipo", Color::from_rgb(70, 130, 180));
+        let (_, por_codigo_postal) = chart("Viviendas por código postal", Color::from_rgb(60, 160, 110));
+        let (_, histograma) = chart("Metros cuadrados", Color::from_rgb(220, 140, 50));
+        principal.end();
+
+        wind.end();
+        wind.resizable(&principal);
+        Graficos { wind, por_tipo, por_codigo_postal, histograma }
+    }
+
+    pub fn show(&mut self) {
+        self.wind.show();
+    }
+
+    pub fn shown(&self) -> bool {
+        self.wind.shown()
+    }
+
+    pub fn update(&mut self, datos: &[TipoVivienda]) {
+        let estadisticas = Estadisticas::calculate(datos);
+        *self.por_tipo.borrow_mut() = series(&estadisticas.por_tipo);
+        *self.por_codigo_postal.borrow_mut() = series(&estadisticas.por_codigo_postal);
+        *self.histograma.borrow_mut() = estadisticas::histogram(datos, INTERVALOS_HISTOGRAMA).into_iter()
+            .map(|(desde, hasta, total)| (format!("{}-{}", desde, hasta), total))
+            .collect();
+        self.wind.redraw();
+    }
+}
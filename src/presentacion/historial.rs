@@ -0,0 +1,140 @@
+use fltk::{app, browser::{Browser, HoldBrowser}, button::Button, dialog, frame::Frame, group::Flex, menu::Choice, prelude::*, window::Window};
+
+use super::{Acciones, WIDGET_HEIGHT, WIDGET_PADDING, WIDGET_WIDTH};
+use crate::entidad::{TipoVivienda, TipoViviendaDAO};
+use crate::versiones::{self, Version};
+
+#[derive(Clone, Copy)]
+enum Accion {
+    Select,
+    Restore,
+}
+
+fn fill(list_browser: &mut HoldBrowser, comparar_choice: &mut Choice, versiones: &[Version]) {
+    list_browser.clear();
+    comparar_choice.clear();
+    comparar_choice.add_choice("Versión anterior");
+    for version in versiones {
+        list_browser.add(&format!("@.v{}  {}  {} ({})", version.numero, version.fecha, version.operacion, version.usuario));
+        comparar_choice.add_choice(&format!("v{}", version.numero));
+    }
+    comparar_choice.set_value(0);
+    if !versiones.is_empty() {
+        list_browser.select(versiones.len() as i32);
+    }
+}
+
+// Diferencias entre la versión seleccionada y la elegida para comparar (por defecto, la anterior)
+fn fill_changes(cambios_browser: &mut Browser, versiones: &[Version], seleccion: usize, comparar: i32) {
+    cambios_browser.clear();
+    let version = &versiones[seleccion];
+    let otra = match comparar {
+        0 if seleccion > 0 => Some(&versiones[seleccion - 1]),
+        0 => None,
+        n => versiones.get((n - 1) as usize),
+    };
+    cambios_browser.add(&format!("@b@.v{}: {} por {} el {}", version.numero, version.operacion, version.usuario, version.fecha));
+    let cambios = match otra {
+        Some(otra) => {
+            cambios_browser.add(&format!("@.Comparada con v{}", otra.numero));
+            versiones::diff_versions(otra, version)
+        }
+        None => versiones::diff(None, serde_json::to_value(&version.datos).ok()),
+    };
+    if cambios.is_empty() {
+        cambios_browser.add("Sin diferencias");
+    }
+    for (campo, antes, despues) in cambios {
+        cambios_browser.add(&format!("@.{}: '{}' -> '{}'", campo, antes, despues));
+    }
+}
+
+// Ventana modal con las versiones de una vivienda; devuelve la versión restaurada, si se restaura alguna
+pub fn show(id: &str, tipoViviendaDAO: &mut TipoViviendaDAO) -> Result<Option<TipoVivienda>, String> {
+    let mut versiones = tipoViviendaDAO.versions(id).map_err(|e| e.to_string())?;
+    let mut restaurada = None;
+
+    let acciones = Acciones::new();
+    let mut wind = Window::default().with_size(WIDGET_WIDTH * 12, WIDGET_HEIGHT * 18).with_label(&format!("Historial de la vivienda {}", id));
+
+    let mut principal = Flex::default_fill().column();
+    principal.set_margin(WIDGET_PADDING);
+    principal.set_pad(WIDGET_PADDING);
+
+    let mut paneles = Flex::default().row();
+    let mut list_browser = HoldBrowser::default();
+    let mut cambios_browser = Browser::default();
+    paneles.set_size(&list_browser, WIDGET_WIDTH * 5);
+    paneles.end();
+
+    let mut fila_botones = Flex::default().row();
+    let label = Frame::default().with_label("Comparar con:").with_align(fltk::enums::Align::Right | fltk::enums::Align::Inside);
+    fila_botones.set_size(&label, WIDGET_WIDTH + WIDGET_PADDING * 2);
+    let mut comparar_choice = Choice::default();
+    fila_botones.set_size(&comparar_choice, WIDGET_WIDTH * 2);
+    Frame::default();
+    let mut restore_button = Button::default().with_label("Restaurar versión");
+    fila_botones.set_size(&restore_button, WIDGET_WIDTH * 2);
+    let mut close_button = Button::default().with_label("Cerrar");
+    fila_botones.set_size(&close_button, WIDGET_WIDTH + WIDGET_PADDING * 2);
+    fila_botones.end();
+    principal.set_size(&fila_botones, WIDGET_HEIGHT);
+
+    principal.end();
+    wind.end();
+    wind.resizable(&principal);
+    wind.make_modal(true);
+
+    acciones.emit(&mut list_browser, Accion::Select);
+    acciones.emit(&mut comparar_choice, Accion::Select);
+    acciones.emit(&mut restore_button, Accion::Restore);
+    let mut ventana = wind.clone();
+    close_button.set_callback(move |_| ventana.hide());
+
+    fill(&mut list_browser, &mut comparar_choice, &versiones);
+    if versiones.is_empty() {
+        cambios_browser.add("No hay versiones registradas");
+        restore_button.deactivate();
+    } else {
+        acciones.send(Accion::Select);
+    }
+    wind.show();
+
+    while wind.shown() {
+        app::wait();
+        let seleccion = match list_browser.value() {
+            0 => None,
+            linea => Some((linea - 1) as usize),
+        };
+        match acciones.recv() {
+            Some(Accion::Select) => {
+                if let Some(seleccion) = seleccion {
+                    fill_changes(&mut cambios_browser, &versiones, seleccion, comparar_choice.value());
+                    if versiones[seleccion].datos.is_some() {
+                        restore_button.activate();
+                    } else {
+                        restore_button.deactivate();
+                    }
+                }
+            }
+            Some(Accion::Restore) => {
+                if let Some(seleccion) = seleccion {
+                    let pregunta = format!("¿Restaurar la versión v{} de la vivienda {} como la actual?\n\nSe guardará inmediatamente como una versión nueva.", versiones[seleccion].numero, id);
+                    if dialog::choice2_default(&pregunta, "Cancelar", "Restaurar", "") == Some(1) {
+                        match tipoViviendaDAO.restore_version(&versiones[seleccion]) {
+                            Ok(tipoVivienda) => {
+                                restaurada = Some(tipoVivienda);
+                                versiones = tipoViviendaDAO.versions(id).map_err(|e| e.to_string())?;
+                                fill(&mut list_browser, &mut comparar_choice, &versiones);
+                                acciones.send(Accion::Select);
+                            }
+                            Err(e) => dialog::alert_default(&format!("No se pudo restaurar la versión: {}", e)),
+                        }
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+    Ok(restaurada)
+}
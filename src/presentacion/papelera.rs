@@ -0,0 +1,101 @@
+use fltk::{app, browser::HoldBrowser, button::Button, dialog, frame::Frame, group::Flex, prelude::*, window::Window};
+
+use super::{Acciones, WIDGET_HEIGHT, WIDGET_PADDING, WIDGET_WIDTH};
+use crate::entidad::{ScreenOutput, TipoVivienda, TipoViviendaDAO};
+
+#[derive(Clone, Copy)]
+enum Accion {
+    Select,
+    Restore,
+    Purge,
+}
+
+fn fill(list_browser: &mut HoldBrowser, borrados: &Vec<(TipoVivienda, String)>) {
+    list_browser.clear();
+    for (tipoVivienda, fecha) in borrados {
+        list_browser.add(&format!("{}  (borrada {})", tipoVivienda.toScreen(), fecha));
+    }
+}
+
+// Ventana modal; devuelve las viviendas restauradas para incorporarlas al modelo
+pub fn show(tipoViviendaDAO: &mut TipoViviendaDAO) -> Result<Vec<TipoVivienda>, String> {
+    let mut borrados = tipoViviendaDAO.deleted().map_err(|e| e.to_string())?;
+    let mut restauradas = Vec::new();
+
+    let acciones = Acciones::new();
+    let mut wind = Window::default().with_size(WIDGET_WIDTH * 9, WIDGET_HEIGHT * 14).with_label("Papelera");
+
+    let mut principal = Flex::default_fill().column();
+    principal.set_margin(WIDGET_PADDING);
+    principal.set_pad(WIDGET_PADDING);
+
+    let mut list_browser = HoldBrowser::default();
+
+    let mut fila_botones = Flex::default().row();
+    Frame::default();
+    let mut restore_button = Button::default().with_label("Restaurar");
+    fila_botones.set_size(&restore_button, WIDGET_WIDTH + WIDGET_PADDING * 2);
+    let mut purge_button = Button::default().with_label("Purgar");
+    fila_botones.set_size(&purge_button, WIDGET_WIDTH + WIDGET_PADDING * 2);
+    fila_botones.end();
+    principal.set_size(&fila_botones, WIDGET_HEIGHT);
+
+    principal.end();
+    wind.end();
+    wind.resizable(&principal);
+    wind.make_modal(true);
+
+    acciones.emit(&mut list_browser, Accion::Select);
+    acciones.emit(&mut restore_button, Accion::Restore);
+    acciones.emit(&mut purge_button, Accion::Purge);
+    restore_button.deactivate();
+    purge_button.deactivate();
+
+    fill(&mut list_browser, &borrados);
+    wind.show();
+
+    while wind.shown() {
+        app::wait();
+        let seleccion = list_browser.value();
+        match acciones.recv() {
+            Some(Accion::Select) => {
+                if seleccion > 0 {
+                    restore_button.activate();
+                    purge_button.activate();
+                } else {
+                    restore_button.deactivate();
+                    purge_button.deactivate();
+                }
+            }
+            Some(Accion::Restore) if seleccion > 0 => {
+                let id = borrados[(seleccion - 1) as usize].0.identificacion.clone();
+                match tipoViviendaDAO.restore(&id) {
+                    Ok(tipoVivienda) => {
+                        restauradas.push(tipoVivienda);
+                        borrados.remove((seleccion - 1) as usize);
+                    }
+                    Err(e) => dialog::alert_default(&format!("No se pudo restaurar {}: {}", id, e)),
+                }
+                fill(&mut list_browser, &borrados);
+                acciones.send(Accion::Select);
+            }
+            Some(Accion::Purge) if seleccion > 0 => {
+                let (tipoVivienda, _) = &borrados[(seleccion - 1) as usize];
+                let id = tipoVivienda.identificacion.clone();
+                let pregunta = format!("La vivienda {} se eliminará definitivamente:\n{}\n\nEsta operación no se puede deshacer.", id, tipoVivienda.toScreen());
+                if dialog::choice2_default(&pregunta, "Cancelar", "Purgar", "") == Some(1) {
+                    match tipoViviendaDAO.purge(&id) {
+                        Ok(()) => {
+                            borrados.remove((seleccion - 1) as usize);
+                        }
+                        Err(e) => dialog::alert_default(&format!("No se pudo purgar {}: {}", id, e)),
+                    }
+                    fill(&mut list_browser, &borrados);
+                    acciones.send(Accion::Select);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(restauradas)
+}
@@ -1,65 +1,494 @@
 use diesel::prelude::*;
+use diesel::connection::SimpleConnection;
+use diesel::r2d2::{ConnectionManager, CustomizeConnection, Pool, PooledConnection};
 use diesel::result::Error;
+use diesel::sql_types::{BigInt, Double, Integer, Text};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use dotenv::dotenv;
 
-use crate::entidad::{TipoViviendaBD};
+use crate::entidad::{Auditoria, Avance, NuevaAuditoria, TipoViviendaBD};
+use crate::schema::auditoria;
 use crate::schema::tipo_viviendas::dsl::*;
 
-use std::env;
+use std::collections::HashMap;
+use std::{env, fmt};
+use chrono::Local;
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+// Columnas por las que se puede agrupar en las estadísticas
+#[derive(Debug, Clone, Copy)]
+pub enum Columna {
+    Tipo,
+    CodigoPostal,
+    Habitaciones,
+    Aseos
+}
+
+impl Columna {
+    fn sql(&self) -> &'static str {
+        match self {
+            Columna::Tipo => "tipo",
+            Columna::CodigoPostal => "codigo_postal",
+            Columna::Habitaciones => "numero_habitaciones",
+            Columna::Aseos => "numero_aseos",
+        }
+    }
+}
+
+#[derive(QueryableByName, Debug)]
+pub struct FilaGrupo {
+    #[diesel(sql_type = Text)]
+    pub clave: String,
+    #[diesel(sql_type = BigInt)]
+    pub total: i64,
+    #[diesel(sql_type = Double)]
+    pub media: f64,
+    #[diesel(sql_type = Double)]
+    pub mediana: f64,
+    #[diesel(sql_type = Integer)]
+    pub minimo: i32,
+    #[diesel(sql_type = Integer)]
+    pub maximo: i32,
+    #[diesel(sql_type = Double)]
+    pub habitaciones_media: f64,
+}
+
+#[derive(QueryableByName, Debug)]
+struct FilaDataVersion {
+    #[diesel(sql_type = BigInt)]
+    data_version: i64,
+}
+
+#[derive(QueryableByName, Debug)]
+struct FilaBusqueda {
+    #[diesel(sql_type = Text)]
+    encontrada: String,
+}
+
+#[cfg(test)]
+#[derive(QueryableByName, Debug)]
+struct FilaJournalMode {
+    #[diesel(sql_type = Text)]
+    journal_mode: String,
+}
+
+#[derive(QueryableByName, Debug)]
+pub struct FilaRecuento {
+    #[diesel(sql_type = Integer)]
+    pub valor: i32,
+    #[diesel(sql_type = BigInt)]
+    pub total: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operacion {
+    Alta,
+    Modificacion,
+    Borrado,
+    Restauracion,
+    Reversion,
+    Purga
+}
+
+impl Operacion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operacion::Alta => "alta",
+            Operacion::Modificacion => "modificacion",
+            Operacion::Borrado => "borrado",
+            Operacion::Restauracion => "restauracion",
+            Operacion::Reversion => "reversion",
+            Operacion::Purga => "purga",
+        }
+    }
+}
+
+// La fila cambió (o se purgó) después de leerla; actual es la fila tal como está ahora
+#[derive(Debug, Clone)]
+pub struct ConflictoVersion {
+    pub identificacion: String,
+    pub leida: i32,
+    pub actual: Option<TipoViviendaBD>,
+}
+
+impl fmt::Display for ConflictoVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.actual {
+            Some(actual) => write!(f, "La vivienda {} ha cambiado desde que se leyó (versión {}, ahora {}, modificada el {})",
+                self.identificacion, self.leida, actual.version, actual.updated_at),
+            None => write!(f, "La vivienda {} ya no existe en la base de datos", self.identificacion),
+        }
+    }
+}
+
+impl std::error::Error for ConflictoVersion {}
+
+// Cada palabra va entre comillas para que lo que escribe el usuario no se lea como sintaxis de FTS5,
+// y con * para que encuentre también las palabras que empiezan por ella
+fn match_expression(texto: &str) -> String {
+    texto.split_whitespace()
+        .map(|palabra| format!("\"{}\"*", palabra.replace('"', "\"\"")))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Usuario del sistema operativo que ejecuta la aplicación
+fn usuario() -> String {
+    env::var("USER").or_else(|_| env::var("USERNAME")).unwrap_or_else(|_| String::from("desconocido"))
+}
+
+// Conexiones abiertas a la vez: la de la ventana y las de las tareas en segundo plano
+const TAMANO_POOL: u32 = 4;
+// Milisegundos que una conexión espera a que otra suelte el bloqueo antes de fallar con SQLITE_BUSY
+const BUSY_TIMEOUT: u32 = 5000;
+
+pub type Conexion = PooledConnection<ConnectionManager<SqliteConnection>>;
+
+// Pragmas que se aplican a cada conexión del pool al abrirla
+#[derive(Debug, Clone, Copy)]
+struct Pragmas {
+    wal: bool,
+}
+
+impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for Pragmas {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
+        // Con WAL los que leen no bloquean al que escribe ni al revés
+        let journal = if self.wal { "PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;" } else { "" };
+        conn.batch_execute(&format!("PRAGMA busy_timeout = {}; {}", BUSY_TIMEOUT, journal))
+            .map_err(diesel::r2d2::Error::QueryError)
+    }
+}
 
 pub struct TipoViviendaRepository {
-    pub conn: SqliteConnection,
+    pool: Pool<ConnectionManager<SqliteConnection>>,
+    conn: Conexion,
+    pub database_url: String,
 
 }
 
 impl TipoViviendaRepository {
 
     pub fn new() -> Self {
+        TipoViviendaRepository::try_new().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_new() -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         dotenv().ok();
 
         let database_url = env::var("DATABASE_URL")
-            .expect("DATABASE_URL must be set");        
+            .map_err(|_| "DATABASE_URL must be set")?;
 
-            TipoViviendaRepository { 
-            conn: SqliteConnection::establish(&database_url).expect(&format!("Error connecting to {}", database_url))
+        TipoViviendaRepository::open(&database_url).map_err(|e| format!("Error connecting to {}: {}", database_url, e).into())
+    }
+
+    pub fn open(database_url: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        // Cada conexión a :memory: es una base de datos distinta, así que ahí solo puede haber una
+        let en_memoria = database_url.ends_with(":memory:");
+        let pool = Pool::builder()
+            .max_size(if en_memoria { 1 } else { TAMANO_POOL })
+            .min_idle(Some(1))
+            .connection_customizer(Box::new(Pragmas { wal: !en_memoria }))
+            .build(ConnectionManager::<SqliteConnection>::new(database_url))?;
+        let mut conn = pool.get()?;
+        conn.run_pending_migrations(MIGRATIONS)?;
+        Ok(TipoViviendaRepository { pool, conn, database_url: database_url.to_string() })
+    }
+
+    // Otro repositorio sobre una conexión distinta del mismo pool, para usarlo desde otro hilo
+    pub fn pooled(&self) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        if self.pool.max_size() < 2 {
+            return Err(format!("{} no admite más de una conexión", self.database_url).into());
         }
+        Ok(TipoViviendaRepository { pool: self.pool.clone(), conn: self.pool.get()?, database_url: self.database_url.clone() })
     }
 
     pub fn find_all(&mut self) -> Result<Vec<TipoViviendaBD>, Error>  {
         {
-            tipo_viviendas.load::<TipoViviendaBD>(&mut self.conn)
+            tipo_viviendas.filter(deleted_at.is_null()).load::<TipoViviendaBD>(&mut self.conn)
         }
     }
 
+    pub fn find_deleted(&mut self) -> Result<Vec<TipoViviendaBD>, Error>  {
+        tipo_viviendas.filter(deleted_at.is_not_null()).order(deleted_at.desc()).load::<TipoViviendaBD>(&mut self.conn)
+    }
+
 
     pub fn find_by_id(&mut self, uniq_id: String) -> Result<TipoViviendaBD, Error> {
         tipo_viviendas.find(uniq_id).get_result::<TipoViviendaBD>(&mut self.conn)
     }
 
     pub fn create(&mut self, new_tipo_vivienda: &TipoViviendaBD) -> Result<TipoViviendaBD, Error> {
-        diesel::insert_into(tipo_viviendas)
-        .values(new_tipo_vivienda)
-        .execute(&mut self.conn)
-        .expect("Error saving new TipoViviendaBD");
+        self.conn.transaction(|conn| TipoViviendaRepository::create_conn(conn, new_tipo_vivienda))
+    }
+
+    fn create_conn(conn: &mut SqliteConnection, new_tipo_vivienda: &TipoViviendaBD) -> Result<TipoViviendaBD, Error> {
+        TipoViviendaRepository::audited(conn, Operacion::Alta, &[new_tipo_vivienda.identificacion.clone()], |conn| {
+            diesel::insert_into(tipo_viviendas).values(new_tipo_vivienda).execute(conn)
+        })?;
+        tipo_viviendas.find(&new_tipo_vivienda.identificacion).first(conn)
+    }
+
+    // Compare-and-swap: tipo_vivienda.version es la versión leída; si la fila ha cambiado desde entonces
+    // no se modifica y se devuelve ConflictoVersion con la fila actual
+    pub fn update(&mut self, uniq_id: String, tipo_vivienda: TipoViviendaBD) -> Result<TipoViviendaBD, Box<dyn std::error::Error + Send + Sync>> {
+        self.conn.immediate_transaction(|conn| TipoViviendaRepository::update_checked(conn, &uniq_id, &tipo_vivienda))
+    }
+
+    fn update_checked(conn: &mut SqliteConnection, uniq_id: &String, tipo_vivienda: &TipoViviendaBD) -> Result<TipoViviendaBD, Box<dyn std::error::Error + Send + Sync>> {
+        let actual = tipo_viviendas.find(uniq_id).first::<TipoViviendaBD>(conn).optional()?;
+        if actual.as_ref().map(|fila| fila.version) != Some(tipo_vivienda.version) {
+            return Err(ConflictoVersion { identificacion: uniq_id.clone(), leida: tipo_vivienda.version, actual }.into());
+        }
+        TipoViviendaRepository::audited(conn, Operacion::Modificacion, &[uniq_id.clone()], |conn| {
+            TipoViviendaRepository::update_conn(conn, tipo_vivienda)
+        })?;
+        Ok(tipo_viviendas.find(uniq_id).first(conn)?)
+    }
+
+    // Un guardado completo en una sola transacción: borradas van a la papelera, altas se crean y
+    // modificaciones se actualizan con compare-and-swap. Si algo falla no se aplica nada
+    pub fn save_all(&mut self, borradas: &[String], altas: &[TipoViviendaBD], modificaciones: &[TipoViviendaBD], avance: Avance)
+    -> Result<Vec<TipoViviendaBD>, Box<dyn std::error::Error + Send + Sync>> {
+        self.conn.immediate_transaction(|conn| {
+            TipoViviendaRepository::delete_conn(conn, borradas)?;
+            let total = altas.len() + modificaciones.len();
+            let mut guardadas = Vec::new();
+            for alta in altas {
+                avance(guardadas.len(), total);
+                guardadas.push(TipoViviendaRepository::create_conn(conn, alta)?);
+            }
+            for modificacion in modificaciones {
+                avance(guardadas.len(), total);
+                guardadas.push(TipoViviendaRepository::update_checked(conn, &modificacion.identificacion, modificacion)?);
+            }
+            Ok(guardadas)
+        })
+    }
+
+    fn update_conn(conn: &mut SqliteConnection, tipo_vivienda: &TipoViviendaBD) -> Result<usize, Error> {
+        let ahora = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        diesel::update(tipo_viviendas.find(&tipo_vivienda.identificacion))
+        .set((calle.eq(&tipo_vivienda.calle),numero.eq(&tipo_vivienda.numero), piso.eq(&tipo_vivienda.piso), codigo_postal.eq(&tipo_vivienda.codigo_postal), metros_cuadrados.eq(&tipo_vivienda.metros_cuadrados), numero_aseos.eq(&tipo_vivienda.numero_aseos),numero_habitaciones.eq(&tipo_vivienda.numero_habitaciones),tipo.eq(&tipo_vivienda.tipo), deleted_at.eq(&tipo_vivienda.deleted_at),
+            precio.eq(&tipo_vivienda.precio), moneda.eq(&tipo_vivienda.moneda), estado.eq(&tipo_vivienda.estado), fecha_alta.eq(&tipo_vivienda.fecha_alta), descripcion.eq(&tipo_vivienda.descripcion),
+            municipio.eq(&tipo_vivienda.municipio), provincia.eq(&tipo_vivienda.provincia), version.eq(version + 1), updated_at.eq(ahora)))
+        .execute(conn)
+    }
 
-        tipo_viviendas.order(identificacion.desc()).first(&mut self.conn)
+    fn upsert_conn(conn: &mut SqliteConnection, tipo_vivienda: &TipoViviendaBD) -> Result<usize, Error> {
+        match TipoViviendaRepository::update_conn(conn, tipo_vivienda)? {
+            0 => diesel::insert_into(tipo_viviendas).values(tipo_vivienda).execute(conn),
+            modificadas => Ok(modificadas),
+        }
+    }
+
+    fn delete_conn(conn: &mut SqliteConnection, uniq_ids: &[String]) -> Result<usize, Error> {
+        let ahora = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        TipoViviendaRepository::audited(conn, Operacion::Borrado, uniq_ids, |conn| {
+            diesel::update(tipo_viviendas.filter(identificacion.eq_any(uniq_ids)).filter(deleted_at.is_null()))
+            .set((deleted_at.eq(Some(ahora.clone())), version.eq(version + 1), updated_at.eq(ahora)))
+            .execute(conn)
+        })
     }
 
-    pub fn update(&mut self, uniq_id: String, tipo_vivienda: TipoViviendaBD) -> Result<TipoViviendaBD, Error> {
-        diesel::update(tipo_viviendas.find(tipo_vivienda.identificacion))
-        .set((calle.eq(&tipo_vivienda.calle),numero.eq(&tipo_vivienda.numero), piso.eq(&tipo_vivienda.piso), codigo_postal.eq(&tipo_vivienda.codigo_postal), metros_cuadrados.eq(&tipo_vivienda.metros_cuadrados), numero_aseos.eq(&tipo_vivienda.numero_aseos),numero_habitaciones.eq(&tipo_vivienda.numero_habitaciones),tipo.eq(&tipo_vivienda.tipo)))
-        .execute(&mut self.conn)
-        .unwrap();
+    // Ejecuta cambio y registra en auditoria las filas de uniq_ids que hayan cambiado, en la misma transacción
+    fn audited<F>(conn: &mut SqliteConnection, operacion: Operacion, uniq_ids: &[String], cambio: F) -> Result<usize, Error>
+    where F: FnOnce(&mut SqliteConnection) -> Result<usize, Error> {
+        let filas = |conn: &mut SqliteConnection| -> Result<HashMap<String, TipoViviendaBD>, Error> {
+            Ok(tipo_viviendas.filter(identificacion.eq_any(uniq_ids)).load::<TipoViviendaBD>(conn)?
+                .into_iter().map(|fila| (fila.identificacion.clone(), fila)).collect())
+        };
+        let antes = filas(conn)?;
+        let resultado = cambio(conn)?;
+        let despues = filas(conn)?;
+
+        let json = |fila: Option<&TipoViviendaBD>| fila.map(serde_json::to_string).transpose()
+            .map_err(|e| Error::SerializationError(Box::new(e)));
+        let fecha = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let usuario = usuario();
+        for uniq_id in uniq_ids {
+            let (fila_antes, fila_despues) = (antes.get(uniq_id), despues.get(uniq_id));
+            if fila_antes == fila_despues {
+                continue;
+            }
+            let operacion = match (fila_antes, fila_despues) {
+                (None, _) if operacion == Operacion::Modificacion => Operacion::Alta,
+                (_, None) => Operacion::Purga,
+                _ => operacion,
+            };
+            diesel::insert_into(auditoria::table)
+            .values(NuevaAuditoria {
+                operacion: operacion.as_str().to_string(),
+                identificacion: uniq_id.clone(),
+                antes: json(fila_antes)?,
+                despues: json(fila_despues)?,
+                fecha: fecha.clone(),
+                usuario: usuario.clone(),
+            })
+            .execute(conn)?;
+        }
+        Ok(resultado)
+    }
 
-        tipo_viviendas.find(uniq_id).first(&mut self.conn)
+    // Fusión de duplicados: la conservada se guarda (o se crea si aún no existía) y las demás van a la papelera
+    pub fn merge(&mut self, conservada: &TipoViviendaBD, descartadas: &[String]) -> Result<usize, Error> {
+        self.conn.transaction(|conn| {
+            TipoViviendaRepository::audited(conn, Operacion::Modificacion, &[conservada.identificacion.clone()], |conn| {
+                TipoViviendaRepository::upsert_conn(conn, conservada)
+            })?;
+            TipoViviendaRepository::delete_conn(conn, descartadas)
+        })
     }
 
+    // Borrado lógico: la vivienda queda en la papelera hasta que se purga
     pub fn delete(&mut self, uniq_id: &String) -> Result<usize, Error> {
-         diesel::delete(tipo_viviendas.find(uniq_id)).execute(&mut self.conn)
+        self.conn.transaction(|conn| TipoViviendaRepository::delete_conn(conn, &[uniq_id.clone()]))
+    }
+
+    pub fn delete_many(&mut self, uniq_ids: &[String]) -> Result<usize, Error> {
+        self.conn.transaction(|conn| TipoViviendaRepository::delete_conn(conn, uniq_ids))
+    }
+
+    pub fn update_tipo_many(&mut self, uniq_ids: &[String], nuevo_tipo: &str) -> Result<usize, Error> {
+        let ahora = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        self.conn.transaction(|conn| {
+            TipoViviendaRepository::audited(conn, Operacion::Modificacion, uniq_ids, |conn| {
+                diesel::update(tipo_viviendas.filter(identificacion.eq_any(uniq_ids)).filter(deleted_at.is_null()))
+                .set((tipo.eq(nuevo_tipo), version.eq(version + 1), updated_at.eq(ahora)))
+                .execute(conn)
+            })
+        })
+    }
+
+    pub fn update_codigo_postal_many(&mut self, uniq_ids: &[String], nuevo_codigo_postal: &str) -> Result<usize, Error> {
+        let ahora = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        self.conn.transaction(|conn| {
+            TipoViviendaRepository::audited(conn, Operacion::Modificacion, uniq_ids, |conn| {
+                diesel::update(tipo_viviendas.filter(identificacion.eq_any(uniq_ids)).filter(deleted_at.is_null()))
+                .set((codigo_postal.eq(nuevo_codigo_postal), version.eq(version + 1), updated_at.eq(ahora)))
+                .execute(conn)
+            })
+        })
+    }
+
+    pub fn restore(&mut self, uniq_id: &String) -> Result<TipoViviendaBD, Error> {
+        self.conn.transaction(|conn| {
+            TipoViviendaRepository::audited(conn, Operacion::Restauracion, &[uniq_id.clone()], |conn| {
+                diesel::update(tipo_viviendas.find(uniq_id))
+                .set((deleted_at.eq(None::<String>), version.eq(version + 1), updated_at.eq(Local::now().format("%Y-%m-%d %H:%M:%S").to_string())))
+                .execute(conn)
+            })?;
+            tipo_viviendas.find(uniq_id).first(conn)
+        })
+    }
+
+    // Vuelve a una versión anterior; se crea de nuevo si se había purgado
+    pub fn restore_version(&mut self, anterior: &TipoViviendaBD) -> Result<TipoViviendaBD, Error> {
+        self.conn.transaction(|conn| {
+            TipoViviendaRepository::audited(conn, Operacion::Reversion, &[anterior.identificacion.clone()], |conn| {
+                TipoViviendaRepository::upsert_conn(conn, anterior)
+            })?;
+            tipo_viviendas.find(&anterior.identificacion).first(conn)
+        })
+    }
+
+    pub fn purge(&mut self, uniq_id: &String) -> Result<usize, Error> {
+        self.conn.transaction(|conn| {
+            TipoViviendaRepository::audited(conn, Operacion::Purga, &[uniq_id.clone()], |conn| {
+                diesel::delete(tipo_viviendas.find(uniq_id).filter(deleted_at.is_not_null())).execute(conn)
+            })
+        })
+    }
+
+    // Versión de cada vivienda, incluidas las de la papelera
+    pub fn versions(&mut self) -> Result<Vec<(String, i32)>, Error> {
+        tipo_viviendas.select((identificacion, version)).load(&mut self.conn)
+    }
+
+    // Viviendas con todas las palabras del texto, de la más a la menos relevante. En bm25 pesan más
+    // el Id, la calle y el código postal que la descripción (mismo orden que las columnas del índice)
+    pub fn search(&mut self, texto: &str) -> Result<Vec<TipoViviendaBD>, Error> {
+        let expresion = match_expression(texto);
+        if expresion.is_empty() {
+            return self.find_all();
+        }
+        let encontradas: Vec<String> = diesel::sql_query(
+            "SELECT tipo_viviendas_fts.identificacion AS encontrada FROM tipo_viviendas_fts
+            JOIN tipo_viviendas ON tipo_viviendas.identificacion = tipo_viviendas_fts.identificacion
+            WHERE tipo_viviendas_fts MATCH ? AND tipo_viviendas.deleted_at IS NULL
+            ORDER BY bm25(tipo_viviendas_fts, 10.0, 5.0, 2.0, 1.0, 3.0, 2.0, 1.0, 2.0, 1.0, 0.5)")
+        .bind::<Text, _>(expresion)
+        .load::<FilaBusqueda>(&mut self.conn)?
+        .into_iter().map(|fila| fila.encontrada).collect();
+        let rango: HashMap<&String, usize> = encontradas.iter().enumerate().map(|(i, id)| (id, i)).collect();
+        let mut filas = tipo_viviendas.filter(identificacion.eq_any(&encontradas)).load::<TipoViviendaBD>(&mut self.conn)?;
+        filas.sort_by_key(|fila| rango.get(&fila.identificacion).copied());
+        Ok(filas)
+    }
+
+    pub fn find_history(&mut self, uniq_id: &str) -> Result<Vec<Auditoria>, Error> {
+        auditoria::table
+        .filter(auditoria::identificacion.eq(uniq_id))
+        .order(auditoria::id.desc())
+        .load::<Auditoria>(&mut self.conn)
+    }
+
+    // Sin columna se obtiene un único grupo con todas las viviendas; la mediana se calcula con funciones de ventana
+    pub fn group_by(&mut self, columna: Option<Columna>) -> Result<Vec<FilaGrupo>, Error> {
+        let clave = columna.map(|c| c.sql()).unwrap_or("''");
+        diesel::sql_query(format!(
+            "WITH filas AS (
+                SELECT CAST({clave} AS TEXT) AS clave, metros_cuadrados, numero_habitaciones,
+                    ROW_NUMBER() OVER (PARTITION BY {clave} ORDER BY metros_cuadrados) AS fila,
+                    COUNT(*) OVER (PARTITION BY {clave}) AS n
+                FROM tipo_viviendas WHERE deleted_at IS NULL)
+            SELECT clave, COUNT(*) AS total, AVG(metros_cuadrados) AS media,
+                AVG(CASE WHEN fila IN ((n + 1) / 2, (n + 2) / 2) THEN metros_cuadrados END) AS mediana,
+                MIN(metros_cuadrados) AS minimo, MAX(metros_cuadrados) AS maximo,
+                AVG(numero_habitaciones) AS habitaciones_media
+            FROM filas GROUP BY clave ORDER BY clave", clave = clave))
+        .load::<FilaGrupo>(&mut self.conn)
+    }
+
+    pub fn count_by(&mut self, columna: Columna) -> Result<Vec<FilaRecuento>, Error> {
+        diesel::sql_query(format!(
+            "SELECT {columna} AS valor, COUNT(*) AS total FROM tipo_viviendas
+            WHERE deleted_at IS NULL GROUP BY {columna} ORDER BY {columna}", columna = columna.sql()))
+        .load::<FilaRecuento>(&mut self.conn)
+    }
+
+    // Cambia cuando otra conexión confirma una transacción en la base de datos
+    pub fn data_version(&mut self) -> Result<i64, Error> {
+        Ok(diesel::sql_query("PRAGMA data_version").get_result::<FilaDataVersion>(&mut self.conn)?.data_version)
     }
 
     pub fn deleteAll(&mut self) -> Result<usize, Error>{
-        diesel::delete(tipo_viviendas).execute(&mut self.conn)
+        self.conn.transaction(|conn| {
+            let todas: Vec<String> = tipo_viviendas.select(identificacion).load(conn)?;
+            TipoViviendaRepository::audited(conn, Operacion::Purga, &todas, |conn| {
+                diesel::delete(tipo_viviendas).execute(conn)
+            })
+        })
     }
     
-}
\ No newline at end of file
+}
+
+#[test]
+fn pool_en_wal_con_conexiones_en_otros_hilos() {
+    let path = env::temp_dir().join(format!("pool-{}.sqlite", std::process::id()));
+    let mut repository = TipoViviendaRepository::open(&path.to_string_lossy()).unwrap();
+    let modo = diesel::sql_query("PRAGMA journal_mode").get_result::<FilaJournalMode>(&mut repository.conn).unwrap();
+    assert_eq!(modo.journal_mode, "wal");
+
+    let mut otro = repository.pooled().unwrap();
+    let hilo = std::thread::spawn(move || {
+        otro.create(&TipoViviendaBD { identificacion: String::from("1"), ..Default::default() }).unwrap();
+    });
+    hilo.join().unwrap();
+    assert_eq!(repository.find_all().unwrap().len(), 1);
+    assert!(TipoViviendaRepository::open(":memory:").unwrap().pooled().is_err());
+    drop(repository);
+    for sufijo in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.to_string_lossy(), sufijo));
+    }
+}
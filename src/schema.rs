@@ -1,5 +1,17 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    auditoria (id) {
+        id -> Integer,
+        operacion -> Text,
+        identificacion -> Text,
+        antes -> Nullable<Text>,
+        despues -> Nullable<Text>,
+        fecha -> Text,
+        usuario -> Text,
+    }
+}
+
 diesel::table! {
     tipo_viviendas (identificacion) {
         identificacion -> Text,
@@ -11,5 +23,20 @@ diesel::table! {
         numero_aseos -> Integer,
         numero_habitaciones -> Integer,
         tipo -> Text,
+        deleted_at -> Nullable<Text>,
+        precio -> Double,
+        moneda -> Text,
+        estado -> Text,
+        fecha_alta -> Text,
+        descripcion -> Text,
+        municipio -> Text,
+        provincia -> Text,
+        version -> Integer,
+        updated_at -> Text,
     }
 }
+
+diesel::allow_tables_to_appear_in_same_query!(
+    auditoria,
+    tipo_viviendas,
+);
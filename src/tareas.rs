@@ -0,0 +1,208 @@
+use std::{path::PathBuf, sync::{atomic::{AtomicBool, Ordering}, mpsc, Arc, Mutex}, thread::{self, JoinHandle}};
+
+use crate::entidad::{Avance, ResumenGuardado, TipoVivienda, TipoViviendaDAO};
+use crate::repository::ConflictoVersion;
+
+// Operaciones largas que se hacen fuera del hilo de la ventana
+#[derive(Debug, Clone)]
+pub enum Trabajo {
+    Save(Vec<TipoVivienda>),
+    Import(PathBuf),
+    Export(PathBuf, Vec<TipoVivienda>),
+}
+
+impl Trabajo {
+    pub fn nombre(&self) -> &'static str {
+        match self {
+            Trabajo::Save(_) => "Guardando",
+            Trabajo::Import(_) => "Importando",
+            Trabajo::Export(_, _) => "Exportando",
+        }
+    }
+
+    // Un guardado a medias dejaría la base de datos y el CSV desparejados
+    pub fn cancellable(&self) -> bool {
+        !matches!(self, Trabajo::Save(_))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Evento {
+    Progress(usize, usize),
+    Saved(Result<ResumenGuardado, String>),
+    Conflict(ConflictoVersion),
+    Imported(PathBuf, Result<Vec<TipoVivienda>, String>),
+    Exported(PathBuf, usize, Result<(), String>),
+    Cancelled,
+}
+
+// Lo que comparten la tarea en curso y quien la ha lanzado
+#[derive(Debug, Clone, Default)]
+pub struct Control {
+    cancelada: Arc<AtomicBool>,
+}
+
+impl Control {
+    pub fn cancel(&self) {
+        self.cancelada.store(true, Ordering::Relaxed);
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.cancelada.load(Ordering::Relaxed)
+    }
+}
+
+// Un único hilo que hace los trabajos en orden, cada uno con su propia conexión a la base de datos
+pub struct Trabajador {
+    trabajos: Option<mpsc::Sender<(Trabajo, Control)>>,
+    hilo: Option<JoinHandle<()>>,
+}
+
+impl Trabajador {
+    // avisar se llama desde el hilo del trabajador con el avance y el resultado de cada trabajo
+    pub fn new<F>(tipoViviendaDAO: Arc<Mutex<TipoViviendaDAO>>, avisar: F) -> Trabajador
+    where F: Fn(Evento) + Send + 'static {
+        let (trabajos, recibidos) = mpsc::channel::<(Trabajo, Control)>();
+        let hilo = thread::spawn(move || {
+            for (trabajo, control) in recibidos {
+                let evento = run(&tipoViviendaDAO, trabajo, &control, &avisar);
+                avisar(evento);
+            }
+        });
+        Trabajador { trabajos: Some(trabajos), hilo: Some(hilo) }
+    }
+
+    pub fn submit(&self, trabajo: Trabajo) -> Control {
+        let control = Control::default();
+        if let Some(trabajos) = &self.trabajos {
+            let _ = trabajos.send((trabajo, control.clone()));
+        }
+        control
+    }
+}
+
+// Al cerrar se espera a que acaben los trabajos pendientes, para no cortar un guardado a medias
+impl Drop for Trabajador {
+    fn drop(&mut self) {
+        self.trabajos.take();
+        if let Some(hilo) = self.hilo.take() {
+            let _ = hilo.join();
+        }
+    }
+}
+
+fn run(tipoViviendaDAO: &Mutex<TipoViviendaDAO>, trabajo: Trabajo, control: &Control, avisar: &dyn Fn(Evento)) -> Evento {
+    // Solo se avisa cuando cambia el porcentaje, para no inundar la cola de mensajes de la ventana
+    let mut porcentaje = None;
+    let mut avance = |hechos: usize, total: usize| {
+        let actual = if total == 0 { 100 } else { hechos * 100 / total };
+        if porcentaje != Some(actual) {
+            porcentaje = Some(actual);
+            avisar(Evento::Progress(hechos, total));
+        }
+        !control.cancelled()
+    };
+    let cancellable = trabajo.cancellable();
+    // El trabajo se hace con una copia del DAO sobre otra conexión del pool, y la ventana sigue usando
+    // la suya mientras tanto. Una base de datos en memoria solo tiene una: ahí se trabaja con el DAO bloqueado
+    let copia = tipoViviendaDAO.lock().unwrap_or_else(|e| e.into_inner()).pooled();
+    let evento = match copia {
+        Ok(mut copia) => match execute(&mut copia, trabajo, &mut avance) {
+            Evento::Saved(Ok(resumen)) => match tipoViviendaDAO.lock().unwrap_or_else(|e| e.into_inner()).adopt(copia) {
+                Ok(()) => Evento::Saved(Ok(resumen)),
+                Err(e) => Evento::Saved(Err(e.to_string())),
+            },
+            evento => evento,
+        },
+        Err(_) => execute(&mut tipoViviendaDAO.lock().unwrap_or_else(|e| e.into_inner()), trabajo, &mut avance),
+    };
+    if cancellable && control.cancelled() { Evento::Cancelled } else { evento }
+}
+
+fn execute(tipoViviendaDAO: &mut TipoViviendaDAO, trabajo: Trabajo, avance: Avance) -> Evento {
+    match trabajo {
+        Trabajo::Save(datos) => match tipoViviendaDAO.save_with(&datos, &mut *avance).and_then(|resumen| tipoViviendaDAO.reload().map(|_| resumen)) {
+            Ok(resumen) => Evento::Saved(Ok(resumen)),
+            Err(e) => match e.downcast_ref::<ConflictoVersion>() {
+                Some(conflicto) => Evento::Conflict(conflicto.clone()),
+                None => Evento::Saved(Err(e.to_string())),
+            },
+        },
+        Trabajo::Import(path) => {
+            let resultado = tipoViviendaDAO.import_with(&path, avance).map_err(|e| e.to_string());
+            Evento::Imported(path, resultado)
+        }
+        Trabajo::Export(path, datos) => {
+            let resultado = tipoViviendaDAO.export_with(&path, &datos, avance).map_err(|e| e.to_string());
+            Evento::Exported(path, datos.len(), resultado)
+        }
+    }
+}
+
+#[test]
+fn trabajador_exporta_importa_y_cancela() {
+    let directorio = std::env::temp_dir().join(format!("tareas-{}", std::process::id()));
+    std::fs::create_dir_all(&directorio).unwrap();
+    let tipoViviendaDAO = Arc::new(Mutex::new(TipoViviendaDAO::open(":memory:").unwrap()));
+    let (sender, receiver) = mpsc::channel();
+    let trabajador = Trabajador::new(tipoViviendaDAO.clone(), move |evento| sender.send(evento).unwrap());
+    let datos: Vec<TipoVivienda> = (1..=50).map(|i| TipoVivienda { numero: i, ..TipoVivienda::example(&i.to_string()) }).collect();
+    // Espera al resultado del trabajo y cuenta los avisos de avance
+    let esperar = || {
+        let mut avisos = 0;
+        loop {
+            match receiver.recv().unwrap() {
+                Evento::Progress(_, _) => avisos += 1,
+                evento => return (evento, avisos),
+            }
+        }
+    };
+
+    let path = directorio.join("viviendas.jsonl");
+    trabajador.submit(Trabajo::Export(path.clone(), datos.clone()));
+    let (evento, avisos) = esperar();
+    assert!(matches!(evento, Evento::Exported(_, 50, Ok(()))));
+    assert!(avisos > 1);
+    trabajador.submit(Trabajo::Import(path.clone()));
+    match esperar().0 {
+        Evento::Imported(_, Ok(importadas)) => assert_eq!(importadas, datos),
+        otro => panic!("{:?}", otro),
+    }
+
+    // Con el DAO ocupado el trabajo espera, y al empezar ya está cancelado
+    let ocupado = tipoViviendaDAO.lock().unwrap();
+    let cancelada = directorio.join("cancelada.csv");
+    trabajador.submit(Trabajo::Export(cancelada.clone(), datos.clone())).cancel();
+    drop(ocupado);
+    assert!(matches!(esperar().0, Evento::Cancelled));
+    assert!(!cancelada.exists());
+    drop(trabajador);
+    std::fs::remove_dir_all(&directorio).unwrap();
+}
+
+#[test]
+fn trabajador_guarda_con_otra_conexion() {
+    let path = std::env::temp_dir().join(format!("trabajador-{}.sqlite", std::process::id()));
+    let tipoViviendaDAO = Arc::new(Mutex::new(TipoViviendaDAO::open(&path.to_string_lossy()).unwrap()));
+    let (sender, receiver) = mpsc::channel();
+    let trabajador = Trabajador::new(tipoViviendaDAO.clone(), move |evento| sender.send(evento).unwrap());
+
+    trabajador.submit(Trabajo::Save(vec![TipoVivienda::example("1")]));
+    loop {
+        match receiver.recv().unwrap() {
+            Evento::Progress(_, _) => {}
+            evento => {
+                assert!(matches!(evento, Evento::Saved(Ok(_))));
+                break;
+            }
+        }
+    }
+    // El DAO compartido se queda con lo guardado y no lo toma por un cambio ajeno
+    let mut tipoViviendaDAO = tipoViviendaDAO.lock().unwrap();
+    assert_eq!(tipoViviendaDAO.asVector(), vec![TipoVivienda::example("1")]);
+    assert!(!tipoViviendaDAO.changed_on_disk().unwrap());
+    drop((tipoViviendaDAO, trabajador));
+    for sufijo in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.to_string_lossy(), sufijo));
+    }
+}
@@ -0,0 +1,94 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::entidad::{Auditoria, TipoViviendaBD};
+
+// Cada operación registrada en auditoria deja una versión: la fila tal como quedó.
+// Tras una purga no queda fila y la versión no tiene datos.
+#[derive(Debug, Clone, Serialize)]
+pub struct Version {
+    pub numero: usize,
+    pub fecha: String,
+    pub usuario: String,
+    pub operacion: String,
+    pub datos: Option<TipoViviendaBD>,
+}
+
+impl Version {
+    fn value(&self) -> Option<Value> {
+        self.datos.as_ref().and_then(|datos| serde_json::to_value(datos).ok())
+    }
+}
+
+// Versiones de la más antigua (1) a la más reciente; historial viene de la más reciente a la más antigua
+pub fn timeline(historial: &[Auditoria]) -> Vec<Version> {
+    historial.iter().rev().enumerate()
+        .map(|(i, registro)| Version {
+            numero: i + 1,
+            fecha: registro.fecha.clone(),
+            usuario: registro.usuario.clone(),
+            operacion: registro.operacion.clone(),
+            datos: registro.despues.as_deref().and_then(|json| serde_json::from_str(json).ok()),
+        })
+        .collect()
+}
+
+// Control de concurrencia: cambian en cada escritura y no interesan al comparar
+const CAMPOS_INTERNOS: [&str; 2] = ["version", "updated_at"];
+
+// Campos distintos entre dos filas en JSON como (campo, valor en antes, valor en despues)
+pub fn diff(antes: Option<Value>, despues: Option<Value>) -> Vec<(String, String, String)> {
+    let objeto = |valor: Option<Value>| match valor {
+        Some(Value::Object(campos)) => campos,
+        _ => Map::new(),
+    };
+    let texto = |valor: Option<&Value>| match valor {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(otro) => otro.to_string(),
+    };
+    let (antes, despues) = (objeto(antes), objeto(despues));
+    let campos: std::collections::BTreeSet<&String> = antes.keys().chain(despues.keys()).collect();
+    campos.into_iter()
+        .filter(|campo| !CAMPOS_INTERNOS.contains(&campo.as_str()))
+        .map(|campo| (campo.clone(), texto(antes.get(campo)), texto(despues.get(campo))))
+        .filter(|(_, antes, despues)| antes != despues)
+        .collect()
+}
+
+pub fn diff_versions(antes: &Version, despues: &Version) -> Vec<(String, String, String)> {
+    diff(antes.value(), despues.value())
+}
+
+#[test]
+fn timeline_y_diff_entre_versiones() {
+    let fila = |metros: i32, borrada: Option<&str>| TipoViviendaBD {
+        identificacion: String::from("1"),
+        metros_cuadrados: metros,
+        deleted_at: borrada.map(String::from),
+        ..Default::default()
+    };
+    let registro = |id: i32, operacion: &str, despues: Option<&TipoViviendaBD>| Auditoria {
+        id,
+        operacion: operacion.to_string(),
+        identificacion: String::from("1"),
+        antes: None,
+        despues: despues.map(|fila| serde_json::to_string(fila).unwrap()),
+        fecha: format!("2026-10-19 10:00:0{}", id),
+        usuario: String::from("ana"),
+    };
+    let historial = vec![
+        registro(4, "purga", None),
+        registro(3, "borrado", Some(&fila(90, Some("2026-10-19")))),
+        registro(2, "modificacion", Some(&fila(90, None))),
+        registro(1, "alta", Some(&fila(80, None))),
+    ];
+    let versiones = timeline(&historial);
+    assert_eq!(versiones.iter().map(|version| version.numero).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+    assert_eq!(versiones[0].operacion, "alta");
+    assert!(versiones[3].datos.is_none());
+    assert_eq!(diff_versions(&versiones[0], &versiones[2]), vec![
+        (String::from("deleted_at"), String::new(), String::from("2026-10-19")),
+        (String::from("metros_cuadrados"), String::from("80"), String::from("90")),
+    ]);
+}